@@ -6,12 +6,390 @@ extern crate rand;
 mod tetris;
 
 use ggez::conf::{ Conf, NumSamples, WindowMode, WindowSetup, Backend, ModuleConf };
+use tetris::view::theme::Theme;
 use tetris::view::window::Window;
-use tetris::model::world::World;
+use tetris::model::world::{World, Mode, PRESETS, seed_from};
+use tetris::model::fumen;
+use tetris::model::bindings::Bindings;
+use tetris::ai::bot::{Bot, Dna, default_dna};
+use tetris::ai::controller::{Player, Strategy};
+use tetris::ai::bench;
+use tetris::ai::expectimax;
+use tetris::ai::mcts::{MctsConfig, Rollout};
+use tetris::ai::network::{self, Network, NetworkBot};
+use tetris::ai::genome::{Genome, generation_path};
+use tetris::ai::tbp;
+use tetris::ai::tournament::{Format, Tournament, Winner};
+use tetris::ai::train::{Evaluator, Training};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Removes `--name value` from the arguments and returns the value
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+	let index = args.iter().position(|arg| arg == name)?;
+	args.remove(index);
+	if index < args.len() { Some(args.remove(index)) } else { None }
+}
+
+/// Removes `--name value` from the arguments and parses the value (`default` if missing)
+fn take_parsed<T: FromStr>(args: &mut Vec<String>, name: &str, default: T) -> T {
+	match take_option(args, name).map(|value| value.parse()) {
+		None => default,
+		Some(Ok(value)) => value,
+		Some(Err(_)) => {
+			leg::error(&format!("Invalid value for `{}`", name), None, None);
+			std::process::exit(1);
+		}
+	}
+}
+
+/// Bots scoring boards with weights take the genomes in turn
+fn assign_genomes<'a>(strategies: impl Iterator<Item = &'a mut Strategy>, genomes: &[Dna]) {
+	if genomes.is_empty() {
+		return;
+	}
+	let weighted = strategies.filter_map(|strategy| match strategy {
+		Strategy::Heuristic(dna) | Strategy::Expectimax(dna, _) => Some(dna),
+		Strategy::Mcts(config) => Some(&mut config.dna),
+		_ => None
+	});
+	for (dna, genome) in weighted.zip(genomes.iter().cycle()) {
+		*dna = genome.clone();
+	}
+}
+
+/// Loads the network of `--network`, if any
+fn take_network(args: &mut Vec<String>) -> Option<Network> {
+	take_option(args, "--network").map(|path| {
+		Network::load(&path).unwrap_or_else(|e| {
+			leg::error(&format!("Could not load `{}`: {}", path, e), None, None);
+			std::process::exit(1);
+		})
+	})
+}
+
+/// Player named in `--players` (human, heuristic, expectimax, mcts, network, random, greedy, tbp or replay)
+fn parse_player(name: &str, depth: usize, mcts: &MctsConfig, network: &Option<Network>, tbp: &Option<String>, replay: &Option<Strategy>) -> Option<Player> {
+	match name.trim() {
+		"human" => Some(Player::Human),
+		"heuristic" | "bot" => Some(Player::Bot(Strategy::Heuristic(default_dna()))),
+		"expectimax" => Some(Player::Bot(Strategy::Expectimax(default_dna(), depth))),
+		"mcts" => Some(Player::Bot(Strategy::Mcts(mcts.clone()))),
+		"network" => network.clone().map(|network| Player::Bot(Strategy::Network(network))),
+		"random" => Some(Player::Bot(Strategy::Random)),
+		"greedy" => Some(Player::Bot(Strategy::Greedy)),
+		"tbp" => tbp.clone().map(|command| Player::Bot(Strategy::External(command))),
+		"replay" => replay.clone().map(Player::Bot),
+		_ => None
+	}
+}
 
 fn main() {
 
-	let args: Vec<String> = std::env::args().collect();
+	let mut args: Vec<String> = std::env::args().collect();
+
+
+	// Commands
+
+	if args.get(1).map(String::as_str) == Some("tbp") {
+		let stdin = std::io::stdin();
+		let stdout = std::io::stdout();
+		tbp::serve(Box::new(Bot::new(&default_dna())), stdin.lock(), stdout.lock())
+			.expect("Lost the connection with the frontend");
+		return;
+	}
+
+	if args.get(1).map(String::as_str) == Some("train") {
+		let evaluator = Evaluator::new(take_parsed(&mut args, "--threads", 0), take_parsed(&mut args, "--pieces", 500))
+			.expect("Could not start the thread pool");
+		let training = Training {
+			population: take_parsed(&mut args, "--population", 100),
+			generations: take_parsed(&mut args, "--generations", 10),
+			games: take_parsed(&mut args, "--games", 10),
+			seed: seed_from(take_parsed(&mut args, "--seed", 0))
+		};
+		let progress = |finished: usize, total: usize| {
+			if finished.is_multiple_of(100) || finished == total {
+				eprint!("\r{}/{} games", finished, total);
+			}
+		};
+		let output = take_option(&mut args, "--output");
+		let best = training.run(&evaluator, &progress, &mut |generation, dna, lines| {
+			eprintln!();
+			leg::info(&format!("Generation {}: {} lines with {:?}", generation, lines, dna), None, None);
+			if let Some(output) = &output {
+				let mut genome = Genome::new(dna);
+				genome.fitness = Some(lines as f64 / training.games.max(1) as f64);
+				genome.generation = Some(generation);
+				if let Err(e) = genome.save(generation_path(output, generation)) {
+					leg::error(&format!("Could not save the genome: {}", e), None, None);
+				}
+			}
+		});
+		println!("{:?}", best);
+		return;
+	}
+
+
+	if args.get(1).map(String::as_str) == Some("train-net") {
+		let seed = seed_from(take_parsed(&mut args, "--seed", 0));
+		let mut network = take_network(&mut args)
+			.unwrap_or_else(|| Network::new(take_parsed(&mut args, "--hidden", 32), seed));
+		let games: u64 = take_parsed(&mut args, "--games", 20);
+		let pieces = take_parsed(&mut args, "--pieces", 300);
+		let horizon = take_parsed(&mut args, "--horizon", 10);
+		let epochs = take_parsed(&mut args, "--epochs", 20);
+		let rate = take_parsed(&mut args, "--rate", 0.01);
+		let rounds: u64 = take_parsed(&mut args, "--self-play", 0);
+		let output = take_option(&mut args, "--output").unwrap_or_else(|| "network.json".into());
+
+		// Regression onto the heuristic bot first, then onto its own games
+		for round in 0..=rounds {
+			let mut samples = vec![];
+			for game in 0..games {
+				let seed = seed_from(round * games + game);
+				samples.extend(if round == 0 {
+					network::samples(&mut Bot::new(&default_dna()), seed, pieces, horizon)
+				}
+				else {
+					network::samples(&mut NetworkBot::new(network.clone()), seed, pieces, horizon)
+				});
+			}
+			let loss = network.fit(&mut samples, epochs, rate, seed);
+			leg::info(&format!("Round {}: {} samples, loss {:.5}", round, samples.len(), loss), None, None);
+		}
+
+		network.save(&output).expect("Could not save the network");
+		return;
+	}
+
+	if args.get(1).map(String::as_str) == Some("search-bench") {
+		let depth = take_parsed(&mut args, "--depth", 3);
+		let pieces = take_parsed(&mut args, "--pieces", 100);
+		let seeds: Vec<[u8; 16]> = (0..take_parsed(&mut args, "--games", 3)).map(seed_from).collect();
+		for depth in 1..=depth {
+			let benchmark = expectimax::Benchmark::run(&default_dna(), depth, &seeds, pieces);
+			println!(
+				"depth {}: {:.1} lines/game, {:.0} nodes/s, {} cache hits",
+				depth, benchmark.mean_lines(), benchmark.nodes_per_second(), benchmark.hits
+			);
+		}
+		return;
+	}
+
+
+	// Starting position
+
+	let start = take_option(&mut args, "--fumen").map(|data| {
+		match fumen::decode(&data) {
+			Ok(pages) if !pages.is_empty() => pages[0].clone(),
+			Ok(_) | Err(_) => {
+				leg::error("Could not load the fumen", None, None);
+				std::process::exit(1);
+			}
+		}
+	});
+
+
+	// External bot (Tetris Bot Protocol engine command line)
+
+	let bot_command = take_option(&mut args, "--tbp");
+
+
+	// Replay (the placements of a fumen)
+
+	let replay = take_option(&mut args, "--replay").map(|data| {
+		match fumen::decode(&data) {
+			Ok(pages) => Strategy::Replay(pages.iter().filter(|p| p.piece).map(|p| p.board.current).collect()),
+			Err(_) => {
+				leg::error("Could not load the replay", None, None);
+				std::process::exit(1);
+			}
+		}
+	});
+
+
+	// Trained weights for the heuristic bots (one file or more, each holding one genome or a list)
+
+	let mut genomes: Vec<Dna> = vec![];
+	while let Some(path) = take_option(&mut args, "--genome") {
+		match Genome::load(&path).and_then(|genomes| genomes.iter().map(Genome::dna).collect::<Result<Vec<_>, _>>()) {
+			Ok(dnas) => genomes.extend(dnas),
+			Err(e) => {
+				leg::error(&format!("Could not load `{}`: {}", path, e), None, None);
+				std::process::exit(1);
+			}
+		}
+	}
+
+
+	// Controllers of the boards (comma separated, see `parse_player`)
+
+	let player_names = take_option(&mut args, "--players");
+	let depth = take_parsed(&mut args, "--depth", 2);
+	let network = take_network(&mut args);
+	let time_limit: u64 = take_parsed(&mut args, "--time-limit", 500);
+	let mcts = MctsConfig {
+		iterations: take_parsed(&mut args, "--iterations", 200),
+		time_limit: if time_limit == 0 { None } else { Some(Duration::from_millis(time_limit)) },
+		rollout: match take_option(&mut args, "--rollout").as_deref() {
+			None | Some("random") => Rollout::Random,
+			Some("heuristic") => Rollout::Heuristic,
+			Some(rollout) => {
+				leg::error(&format!("Unknown rollout `{}`", rollout), None, None);
+				std::process::exit(1);
+			}
+		},
+		rollout_depth: take_parsed(&mut args, "--rollout-depth", 3),
+		dna: default_dna()
+	};
+
+
+	// Pieces per second of the bots
+
+	let pps = match take_option(&mut args, "--pps").map(|pps| pps.parse::<f32>()) {
+		None => 2.0,
+		Some(Ok(pps)) if pps > 0.0 => pps,
+		Some(_) => {
+			leg::error("The pieces per second must be a positive number", None, None);
+			std::process::exit(1);
+		}
+	};
+
+
+	// Seed of the pieces (random if not given)
+
+	let seed: u64 = take_option(&mut args, "--seed")
+		.map(|seed| seed.parse().unwrap_or_else(|_| {
+			leg::error("The seed must be a number", None, None);
+			std::process::exit(1);
+		}))
+		.unwrap_or_else(rand::random);
+
+
+	// Coach reviewing the placements of the humans (with the first genome given)
+
+	let coach = args.iter().position(|arg| arg == "--coach").map(|index| {
+		args.remove(index);
+		genomes.first().cloned().unwrap_or_else(default_dna)
+	});
+
+
+	// Bots of `bench` and `tournament`, named as in `--players`
+
+	let parse_bot = |name: &str| match parse_player(name, depth, &mcts, &network, &bot_command, &replay) {
+		Some(Player::Bot(strategy)) => (name.trim().to_string(), strategy),
+		_ => {
+			leg::error(&format!("Unknown bot `{}`", name), None, None);
+			std::process::exit(1);
+		}
+	};
+
+
+	// Benchmark of bots on the same games
+
+	if args.get(1).map(String::as_str) == Some("bench") {
+		let names = player_names.unwrap_or_else(|| "heuristic,greedy,random".into());
+		let mut bots: Vec<(String, Strategy)> = names.split(',')
+			.map(parse_bot)
+			.collect();
+		assign_genomes(bots.iter_mut().map(|(_, strategy)| strategy), &genomes);
+
+		let seeds: Vec<u64> = (0..take_parsed(&mut args, "--games", 20)).collect();
+		let games = seeds.len();
+		let report = bench::Report::run(&bots, &seeds, take_parsed(&mut args, "--pieces", 500), &mut |name, game| {
+			eprint!("\r{}: {}/{} games  ", name, game, games);
+		}).unwrap_or_else(|name| {
+			eprintln!();
+			leg::error(&format!("Could not build `{}`", name), None, None);
+			std::process::exit(1);
+		});
+		eprintln!();
+
+		for bot in &report.bots {
+			leg::info(&format!(
+				"{}: {:.1} lines (median {:.1}, 95% CI {:.1}-{:.1}), {:.1} pieces, {:.0} points, {:.1} PPS",
+				bot.name, bot.lines.mean, bot.lines.median, bot.lines.ci95.0, bot.lines.ci95.1,
+				bot.pieces.mean, bot.score.mean, bot.pps.mean
+			), None, None);
+		}
+
+		let json = serde_json::to_string_pretty(&report).expect("Could not write the report");
+		match take_option(&mut args, "--output") {
+			Some(path) => std::fs::write(&path, json).expect("Could not write the report"),
+			None => println!("{}", json)
+		}
+		return;
+	}
+
+
+	// Tournament of bots playing versus games
+
+	if args.get(1).map(String::as_str) == Some("tournament") {
+		let names = player_names.unwrap_or_else(|| "heuristic,greedy,random".into());
+		let mut bots: Vec<(String, Strategy)> = names.split(',')
+			.map(parse_bot)
+			.collect();
+		assign_genomes(bots.iter_mut().map(|(_, strategy)| strategy), &genomes);
+
+		let format = match take_option(&mut args, "--format").as_deref() {
+			None | Some("round-robin") => Format::RoundRobin,
+			Some("swiss") => Format::Swiss(take_parsed(&mut args, "--rounds", 3)),
+			Some(format) => {
+				leg::error(&format!("Unknown format `{}`", format), None, None);
+				std::process::exit(1);
+			}
+		};
+
+		let mut tournament = Tournament::new(bots, take_parsed(&mut args, "--games", 2), take_parsed(&mut args, "--pieces", 1000));
+		tournament.run(format, &mut |first, second, winner| {
+			let result = match winner {
+				Winner::First => format!("{} beats {}", first, second),
+				Winner::Second => format!("{} beats {}", second, first),
+				Winner::Draw => format!("{} draws with {}", first, second)
+			};
+			leg::info(&result, None, None);
+		}).unwrap_or_else(|name| {
+			leg::error(&format!("Could not build `{}`", name), None, None);
+			std::process::exit(1);
+		});
+
+		let table = tournament.table();
+		println!("{:<4}{:<16}{:>7}{:>5}{:>5}{:>5}{:>8}{:>7}{:>13}", "#", "Player", "Played", "W", "D", "L", "Points", "Elo", "Glicko");
+		for (rank, standing) in table.iter().enumerate() {
+			println!(
+				"{:<4}{:<16}{:>7}{:>5}{:>5}{:>5}{:>8.1}{:>7.0}{:>8.0} ±{:>3.0}",
+				rank + 1, standing.name, standing.played(), standing.wins, standing.draws, standing.losses,
+				standing.points(), standing.elo, standing.glicko, standing.glicko_deviation
+			);
+		}
+
+		if let Some(path) = take_option(&mut args, "--output") {
+			let json = serde_json::to_string_pretty(&table).expect("Could not write the standings");
+			std::fs::write(&path, json).expect("Could not write the standings");
+		}
+		return;
+	}
+
+
+	// Keys of the human boards (written back when changed in the settings)
+
+	let bindings = Bindings::load(take_option(&mut args, "--keys").unwrap_or_else(|| "keys.toml".into()).into())
+		.unwrap_or_else(|e| {
+			leg::error(&format!("Could not load the key bindings: {}", e), None, None);
+			std::process::exit(1);
+		});
+
+
+	// Theme, built in or from a file
+
+	let theme = take_option(&mut args, "--theme").map_or_else(Theme::default, |theme| {
+		Theme::find(&theme).map_or_else(|| Theme::load(&theme), Ok).unwrap_or_else(|e| {
+			leg::error(&format!("Could not load the theme: {}", e), None, None);
+			std::process::exit(1);
+		})
+	});
+
 
 	// Rows
 
@@ -41,6 +419,31 @@ fn main() {
 		true
 	};
 
+	let mut players: Vec<Player> = match player_names {
+		Some(names) => names.split(',')
+			.map(|name| parse_player(name, depth, &mcts, &network, &bot_command, &replay).unwrap_or_else(|| {
+				leg::error(&format!("Unknown player `{}`", name), None, None);
+				std::process::exit(1);
+			}))
+			.collect(),
+		None => {
+			let bot = match &bot_command {
+				Some(command) => Player::Bot(Strategy::External(command.clone())),
+				None => Player::Bot(Strategy::Heuristic(default_dna()))
+			};
+			let mut players = vec![bot; nrows * ncols];
+			if has_player {
+				players[0] = Player::Human;
+			}
+			players
+		}
+	};
+
+	assign_genomes(players.iter_mut().filter_map(|player| match player {
+		Player::Bot(strategy) => Some(strategy),
+		Player::Human => None
+	}), &genomes);
+
 
 	// Config
 
@@ -63,9 +466,6 @@ fn main() {
 	};
 
 
-	// Seed
-
-	let seed: [u8; 16] = rand::random();
 
 
 	// World
@@ -73,11 +473,21 @@ fn main() {
 	let world = World {
 		nrows,
 		ncols,
-		has_player,
+		players,
+		pps,
 		config,
-		seed
-
+		seed,
+		mode: Mode::Marathon,
+		gravity: PRESETS[1].gravity,
+		pause_on_focus_loss: true,
+		start,
+		coach,
+		bindings,
+		theme
 	};
 
-	Window::new(world).run()
+	if let Err(name) = Window::new(world).run() {
+		leg::error(&format!("Could not build `{}`", name), None, None);
+		std::process::exit(1);
+	}
 }
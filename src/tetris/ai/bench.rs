@@ -0,0 +1,154 @@
+extern crate serde;
+
+use std::time::Instant;
+use serde::Serialize;
+use super::super::model::world::seed_from;
+use super::controller::Strategy;
+use super::train;
+
+/// Spread of a measure over the games of a bot
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Summary {
+	pub mean: f64,
+	pub median: f64,
+	pub std_dev: f64,
+	/// 95% confidence interval of the mean
+	pub ci95: (f64, f64)
+}
+
+impl Summary {
+
+	pub fn new(values: &[f64]) -> Self {
+
+		let n = values.len().max(1) as f64;
+		let mean = values.iter().sum::<f64>() / n;
+
+		let mut sorted = values.to_vec();
+		sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+		let median = match sorted.len() {
+			0 => 0.0,
+			len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0,
+			len => sorted[len / 2]
+		};
+
+		let variance = if values.len() > 1 {
+			values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)
+		}
+		else {
+			0.0
+		};
+		let std_dev = variance.sqrt();
+		let margin = 1.96 * std_dev / n.sqrt();
+
+		Self { mean, median, std_dev, ci95: (mean - margin, mean + margin) }
+	}
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GameReport {
+	pub seed: u64,
+	pub lines: usize,
+	pub pieces: usize,
+	pub score: usize,
+	/// Pieces per second of thinking
+	pub pps: f64
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BotReport {
+	pub name: String,
+	pub lines: Summary,
+	pub pieces: Summary,
+	pub score: Summary,
+	pub pps: Summary,
+	pub games: Vec<GameReport>
+}
+
+/// Results of every bot on the same games, as written by the `bench` command
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+	pub seeds: Vec<u64>,
+	pub max_pieces: usize,
+	pub bots: Vec<BotReport>
+}
+
+impl Report {
+
+	/// Plays a headless game per seed with every bot, one after another so the timings
+	/// are not disturbed. `progress` gets the name of the bot and the game finished.
+	/// Fails with the name of the first bot that cannot be built (an engine that does not start).
+	pub fn run(bots: &[(String, Strategy)], seeds: &[u64], max_pieces: usize, progress: &mut dyn FnMut(&str, usize)) -> Result<Self, String> {
+
+		let mut reports = vec![];
+
+		for (name, strategy) in bots {
+
+			let mut games: Vec<GameReport> = vec![];
+			for (index, seed) in seeds.iter().enumerate() {
+				let mut controller = strategy.build(seed_from(*seed)).ok_or_else(|| name.clone())?;
+				let start = Instant::now();
+				let outcome = train::play(controller.as_mut(), seed_from(*seed), max_pieces);
+				let seconds = start.elapsed().as_secs_f64();
+				progress(name, index + 1);
+				games.push(GameReport {
+					seed: *seed,
+					lines: outcome.lines,
+					pieces: outcome.pieces,
+					score: outcome.score,
+					pps: outcome.pieces as f64 / seconds.max(1e-9)
+				});
+			}
+
+			let summary = |measure: &dyn Fn(&GameReport) -> f64| {
+				Summary::new(&games.iter().map(measure).collect::<Vec<_>>())
+			};
+
+			reports.push(BotReport {
+				name: name.clone(),
+				lines: summary(&|g| g.lines as f64),
+				pieces: summary(&|g| g.pieces as f64),
+				score: summary(&|g| g.score as f64),
+				pps: summary(&|g| g.pps),
+				games
+			});
+		}
+
+		Ok(Self {
+			seeds: seeds.to_vec(),
+			max_pieces,
+			bots: reports
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	#[test]
+	fn bench_summary_test() {
+		let summary = Summary::new(&[1.0, 2.0, 3.0, 10.0]);
+		assert_eq!(4.0, summary.mean);
+		assert_eq!(2.5, summary.median);
+		assert!((summary.std_dev - (50.0_f64 / 3.0).sqrt()).abs() < 1e-12);
+		assert!(summary.ci95.0 < 4.0 && 4.0 < summary.ci95.1);
+		assert_eq!(Summary::new(&[7.0]).ci95, (7.0, 7.0));
+	}
+
+	#[test]
+	fn bench_run_test() {
+		let bots = vec![("greedy".to_string(), Strategy::Greedy), ("random".to_string(), Strategy::Random)];
+		let mut finished = 0;
+		let report = Report::run(&bots, &[1, 2], 10, &mut |_, _| finished += 1).unwrap();
+		assert_eq!(4, finished);
+		assert_eq!(2, report.bots.len());
+		assert_eq!(10.0, report.bots[0].pieces.mean);
+		let again = Report::run(&bots, &[1, 2], 10, &mut |_, _| ()).unwrap();
+		assert_eq!(report.bots[1].lines, again.bots[1].lines);
+
+		// An engine that does not start is not a bot without lines
+		let missing = vec![("tbp".to_string(), Strategy::External("./no-such-engine".into()))];
+		assert_eq!(Some("tbp".to_string()), Report::run(&missing, &[1], 10, &mut |_, _| ()).err());
+	}
+}
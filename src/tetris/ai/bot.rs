@@ -1,32 +1,69 @@
 
-use super::super::model::shape;
-use super::super::model::board::Board;
+use std::collections::BTreeMap;
+use super::super::model::board::{Board, Piece};
+use super::controller::Controller;
+use super::features::{self, Feature};
+use super::search;
 
-pub type Dna = [f64; 4];
+/// Weight of each feature of the registry, by name (features left out weigh nothing)
+pub type Dna = BTreeMap<String, f64>;
+
+/// Weights found by Yiyuan Lee's genetic algorithm, a sensible starting point
+pub fn default_dna() -> Dna {
+	[("height", -0.510066), ("lines", 0.760666), ("holes", -0.35663), ("bumpiness", -0.184483)]
+		.iter()
+		.map(|(name, weight)| (name.to_string(), *weight))
+		.collect()
+}
 
 pub struct Bot {
-	dna: Dna
+	/// Features with a weight, looked up once
+	features: Vec<(&'static Feature, f64)>,
+	/// Score of the last placement chosen
+	last: Option<f64>
 }
 
 impl Bot {
 
-	pub fn new(dna: Dna) -> Self {
+	/// Unknown features are ignored, genomes are checked when loaded
+	pub fn new(dna: &Dna) -> Self {
 		Self {
-			dna
+			features: dna.iter()
+				.filter(|(_, weight)| **weight != 0.0)
+				.filter_map(|(name, weight)| features::find(name).map(|feature| (feature, *weight)))
+				.collect(),
+			last: None
 		}
 	}
 
-	fn calc_score(&self, board: &Board) -> f64 {
-		0.0
+	/// Scores the board left after a placement that cleared `lines` lines
+	pub fn calc_score(&self, board: &Board, lines: usize) -> f64 {
+		self.features.iter()
+			.map(|(feature, weight)| weight * (feature.eval)(board, lines))
+			.sum()
 	}
+}
+
+impl Controller for Bot {
 
-	/// Calculates the desired position of the piece
-	/// Returns a number in the range [2..9) as the x position and a Rotation.
-	pub fn ask(&self, board: &Board) -> (usize, shape::Rotation) {
+	/// Picks the placement with the best score
+	fn ask(&mut self, board: &Board) -> Option<Piece> {
 
-		let position = 4;
-		let rotation= shape::Rotation::Rotate0;
+		let mut best: Option<(f64, Piece)> = None;
+
+		for piece in search::placements(board) {
+			let (next, lines) = search::simulate(board, piece);
+			let score = self.calc_score(&next, lines);
+			if best.is_none_or(|(best_score, _)| score > best_score) {
+				best = Some((score, piece));
+			}
+		}
+
+		self.last = best.map(|(score, _)| score);
+		best.map(|(_, piece)| piece)
+	}
 
-		(position, rotation)
+	fn evaluation(&self) -> Option<f64> {
+		self.last
 	}
 }
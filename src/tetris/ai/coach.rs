@@ -0,0 +1,145 @@
+
+use super::super::model::board::{Board, Piece};
+use super::super::model::fumen::{self, Page};
+use super::bot::{Bot, Dna};
+use super::controller::Controller;
+use super::search;
+
+/// How a placement of the human compares to the one the bot prefers
+#[derive(Clone)]
+pub struct Review {
+	/// Placements before this one
+	pub index: usize,
+	/// Board when the piece spawned
+	pub board: Board,
+	pub played: Piece,
+	pub best: Piece,
+	/// Scores of the boards both placements leave
+	pub played_score: f64,
+	pub best_score: f64
+}
+
+impl Review {
+
+	pub fn matched(&self) -> bool {
+		let mut played = self.played.cells();
+		let mut best = self.best.cells();
+		played.sort();
+		best.sort();
+		played == best
+	}
+
+	/// How much worse than the bot the placement scored
+	pub fn loss(&self) -> f64 {
+		(self.best_score - self.played_score).max(0.0)
+	}
+
+	/// Two pages: the placement played, then the one of the bot
+	pub fn fumen(&self) -> String {
+		let page = |piece: Piece, comment: &str| {
+			let mut board = self.board.clone();
+			board.current = piece;
+			Page { board, piece: true, comment: Some(comment.into()) }
+		};
+		fumen::encode(&[page(self.played, "played"), page(self.best, "coach")])
+	}
+}
+
+/// Compares the placements of a human to the top choice of the heuristic bot
+pub struct Coach {
+	bot: Bot,
+	/// Board when the current piece spawned, and where the bot would put it
+	spawned: Option<(Board, Option<Piece>)>,
+	pub reviews: Vec<Review>
+}
+
+impl Coach {
+
+	pub fn new(dna: &Dna) -> Self {
+		Self {
+			bot: Bot::new(dna),
+			spawned: None,
+			reviews: vec![]
+		}
+	}
+
+	/// Lets the bot choose for a piece that just spawned
+	pub fn observe(&mut self, board: &Board) {
+		self.spawned = Some((board.clone(), self.bot.ask(board)));
+	}
+
+	/// Compares the placement of the piece observed last to the choice of the bot
+	pub fn review(&mut self, played: Piece) -> Option<&Review> {
+
+		let (board, best) = self.spawned.take()?;
+		let best = best?;
+
+		let score = |piece: Piece| {
+			let (after, lines) = search::simulate(&board, piece);
+			self.bot.calc_score(&after, lines)
+		};
+		let review = Review {
+			index: self.reviews.len(),
+			played_score: score(played),
+			best_score: score(best),
+			board,
+			played,
+			best
+		};
+
+		self.reviews.push(review);
+		self.reviews.last()
+	}
+
+	/// Share of the placements matching the bot (1 if none yet)
+	pub fn accuracy(&self) -> f64 {
+		if self.reviews.is_empty() {
+			return 1.0;
+		}
+		self.reviews.iter().filter(|r| r.matched()).count() as f64 / self.reviews.len() as f64
+	}
+
+	/// The `count` placements that lost the most to the bot, worst first
+	pub fn worst(&self, count: usize) -> Vec<&Review> {
+		let mut reviews: Vec<&Review> = self.reviews.iter().filter(|r| !r.matched()).collect();
+		reviews.sort_by(|a, b| b.loss().partial_cmp(&a.loss()).unwrap());
+		reviews.truncate(count);
+		reviews
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::bot::default_dna;
+
+	#[test]
+	fn coach_review_test() {
+		let board = Board::new([6; 16]);
+		let mut coach = Coach::new(&default_dna());
+		assert!(coach.review(board.current).is_none());
+
+		// The bot's own choice matches
+		coach.observe(&board);
+		let best = Bot::new(&default_dna()).ask(&board).unwrap();
+		assert!(coach.review(best).unwrap().matched());
+
+		// A worse placement is recorded with what it lost
+		coach.observe(&board);
+		let bot = Bot::new(&default_dna());
+		let score = |piece: &Piece| {
+			let (after, lines) = search::simulate(&board, *piece);
+			bot.calc_score(&after, lines)
+		};
+		let worse = search::placements(&board).into_iter()
+			.min_by(|a, b| score(a).partial_cmp(&score(b)).unwrap())
+			.unwrap();
+		let review = coach.review(worse).unwrap();
+		assert!(!review.matched() && review.loss() > 0.0);
+
+		assert_eq!(0.5, coach.accuracy());
+		assert_eq!(1, coach.worst(5).len());
+		assert_eq!(1, coach.worst(5)[0].index);
+	}
+}
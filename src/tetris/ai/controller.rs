@@ -0,0 +1,96 @@
+
+use super::super::model::board::{Board, Piece};
+use super::bot::{Bot, Dna};
+use super::expectimax::Expectimax;
+use super::greedy::GreedyBot;
+use super::mcts::{Mcts, MctsConfig};
+use super::network::{Network, NetworkBot};
+use super::random::RandomBot;
+use super::replay::Replay;
+use super::tbp::TbpBot;
+
+/// Anything that can play a board: it observes the board when a piece
+/// spawns and decides where that piece should end up
+pub trait Controller {
+
+	/// Final position (resting on the stack) for the current piece, or `None`
+	/// to let it fall where it is
+	fn ask(&mut self, board: &Board) -> Option<Piece>;
+
+	/// Score of the last placement chosen, for the bots that score them
+	fn evaluation(&self) -> Option<f64> {
+		None
+	}
+}
+
+#[derive(Clone)]
+pub enum Player {
+	Human,
+	Bot(Strategy)
+}
+
+#[derive(Clone)]
+pub enum Strategy {
+	/// Our heuristic bot with the weights given
+	Heuristic(Dna),
+	/// Heuristic bot looking some placements ahead
+	Expectimax(Dna, usize),
+	/// Monte Carlo tree search
+	Mcts(MctsConfig),
+	/// Scores the boards with a trained network
+	Network(Network),
+	Random,
+	/// Clears lines first, then keeps the stack clean
+	Greedy,
+	/// Engine launched from a command line, driven through the Tetris Bot Protocol
+	External(String),
+	/// Plays back recorded placements
+	Replay(Vec<Piece>)
+}
+
+impl Player {
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Player::Human => "human",
+			Player::Bot(strategy) => strategy.name()
+		}
+	}
+}
+
+impl Strategy {
+
+	/// Name used by `--players`
+	pub fn name(&self) -> &'static str {
+		match self {
+			Strategy::Heuristic(_) => "heuristic",
+			Strategy::Expectimax(_, _) => "expectimax",
+			Strategy::Mcts(_) => "mcts",
+			Strategy::Network(_) => "network",
+			Strategy::Random => "random",
+			Strategy::Greedy => "greedy",
+			Strategy::External(_) => "tbp",
+			Strategy::Replay(_) => "replay"
+		}
+	}
+
+	/// Builds the controller. Fails if an external engine cannot be started.
+	pub fn build(&self, seed: [u8; 16]) -> Option<Box<dyn Controller>> {
+		match self {
+			Strategy::Heuristic(dna) => Some(Box::new(Bot::new(dna))),
+			Strategy::Expectimax(dna, depth) => Some(Box::new(Expectimax::new(dna, *depth))),
+			Strategy::Mcts(config) => Some(Box::new(Mcts::new(config.clone(), seed))),
+			Strategy::Network(network) => Some(Box::new(NetworkBot::new(network.clone()))),
+			Strategy::Random => Some(Box::new(RandomBot::new(seed))),
+			Strategy::Greedy => Some(Box::new(GreedyBot)),
+			Strategy::External(command) => match TbpBot::spawn(command) {
+				Ok(bot) => Some(Box::new(bot)),
+				Err(e) => {
+					leg::error(&format!("Could not start `{}`: {}", command, e), "TBP".into(), None);
+					None
+				}
+			},
+			Strategy::Replay(placements) => Some(Box::new(Replay::new(placements.clone())))
+		}
+	}
+}
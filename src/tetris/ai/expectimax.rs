@@ -0,0 +1,239 @@
+
+use std::collections::HashMap;
+use std::time::Instant;
+use super::super::model::board::{Board, Piece};
+use super::super::model::shape::Shape;
+use super::bot::{Bot, Dna};
+use super::controller::Controller;
+use super::search;
+use super::train::{self, Outcome};
+
+/// Value of a position where the pieces cannot be placed anymore
+const LOSS: f64 = -1.0e6;
+
+/// The cache is cleared when it holds this many boards
+const CACHE_LIMIT: usize = 1 << 20;
+
+/// Board (grid), shape to place, next shape if known, placements left and lines cleared so far
+type Key = (Vec<u8>, u8, Option<u8>, usize, usize);
+
+/// Looks `depth` placements ahead: the current piece and the preview are known,
+/// the pieces after them are averaged over the seven shapes. Leaves are scored
+/// with the heuristic bot, with every line cleared on the way there.
+pub struct Expectimax {
+	bot: Bot,
+	depth: usize,
+	cache: HashMap<Key, f64>,
+	/// Value of the last placement chosen
+	last: Option<f64>,
+	/// Boards evaluated and boards found in the cache so far
+	pub nodes: usize,
+	pub hits: usize
+}
+
+impl Expectimax {
+
+	pub fn new(dna: &Dna, depth: usize) -> Self {
+		Self {
+			bot: Bot::new(dna),
+			depth: depth.max(1),
+			cache: HashMap::new(),
+			last: None,
+			nodes: 0,
+			hits: 0
+		}
+	}
+
+	/// Best placement of the current piece followed by `depth - 1` more, `cleared` lines into the search
+	fn best(&mut self, board: &Board, following: Option<Shape>, depth: usize, cleared: usize) -> Option<(f64, Piece)> {
+
+		let mut best: Option<(f64, Piece)> = None;
+
+		for piece in search::placements(board) {
+
+			let (next, lines) = search::simulate(board, piece);
+			let lines = cleared + lines;
+			self.nodes += 1;
+
+			let value = if next.is_gameover() {
+				LOSS
+			}
+			else if depth <= 1 {
+				self.bot.calc_score(&next, lines)
+			}
+			else {
+				match following {
+					Some(shape) => self.value(&next, shape, None, depth - 1, lines),
+					None => (1..8)
+						.map(|index| self.value(&next, Shape::from_index(index).unwrap(), None, depth - 1, lines))
+						.sum::<f64>() / 7.0
+				}
+			};
+
+			if best.is_none_or(|(best_value, _)| value > best_value) {
+				best = Some((value, piece));
+			}
+		}
+
+		best
+	}
+
+	/// Cached value of placing `shape` on the board (and what follows)
+	fn value(&mut self, board: &Board, shape: Shape, following: Option<Shape>, depth: usize, cleared: usize) -> f64 {
+
+		let key = (board.grid.as_slice().to_vec(), shape.index(), following.map(Shape::index), depth, cleared);
+		if let Some(value) = self.cache.get(&key) {
+			self.hits += 1;
+			return *value;
+		}
+
+		let mut child = board.clone();
+		child.current = board.spawn(shape);
+		let value = self.best(&child, following, depth, cleared).map_or(LOSS, |(value, _)| value);
+
+		if self.cache.len() >= CACHE_LIMIT {
+			self.cache.clear();
+		}
+		self.cache.insert(key, value);
+
+		value
+	}
+}
+
+impl Controller for Expectimax {
+
+	fn ask(&mut self, board: &Board) -> Option<Piece> {
+		let depth = self.depth;
+		let best = self.best(board, Some(board.collector.get_next()), depth, 0);
+		self.last = best.map(|(value, _)| value);
+		best.map(|(_, piece)| piece)
+	}
+
+	fn evaluation(&self) -> Option<f64> {
+		self.last
+	}
+}
+
+/// Strength and speed of the search at some depth
+pub struct Benchmark {
+	pub outcomes: Vec<Outcome>,
+	pub nodes: usize,
+	pub hits: usize,
+	pub seconds: f64
+}
+
+impl Benchmark {
+
+	/// Plays a headless game per seed
+	pub fn run(dna: &Dna, depth: usize, seeds: &[[u8; 16]], max_pieces: usize) -> Self {
+
+		let mut bot = Expectimax::new(dna, depth);
+		let start = Instant::now();
+		let outcomes = seeds.iter()
+			.map(|seed| train::play(&mut bot, *seed, max_pieces))
+			.collect();
+
+		Self {
+			outcomes,
+			nodes: bot.nodes,
+			hits: bot.hits,
+			seconds: start.elapsed().as_secs_f64()
+		}
+	}
+
+	pub fn mean_lines(&self) -> f64 {
+		self.outcomes.iter().map(|o| o.lines).sum::<usize>() as f64 / self.outcomes.len().max(1) as f64
+	}
+
+	pub fn nodes_per_second(&self) -> f64 {
+		self.nodes as f64 / self.seconds.max(1e-9)
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::bot::default_dna;
+	use super::super::super::model::board;
+
+	const BOARD: &str = "
+		##########
+		#........#
+		#...t....#
+		#..ttt...#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#I.......#
+		#IOO.SS..#
+		#IOOSSZZ.#
+		##########
+	";
+
+	#[test]
+	fn expectimax_depth_one_test() {
+		let board: Board = BOARD.parse().unwrap();
+		let expected = Bot::new(&default_dna()).ask(&board).unwrap().cells();
+		let piece = Expectimax::new(&default_dna(), 1).ask(&board).unwrap();
+		assert_eq!(expected, piece.cells());
+	}
+
+	#[test]
+	fn expectimax_cache_test() {
+		let board: Board = BOARD.parse().unwrap();
+		let mut bot = Expectimax::new(&default_dna(), 2);
+
+		let piece = bot.ask(&board).unwrap();
+		assert!(!board::collides(&board, &piece) && board::down(&board, &piece).is_err());
+
+		// Every reply is known the second time
+		let nodes = bot.nodes;
+		assert_eq!(piece.cells(), bot.ask(&board).unwrap().cells());
+		assert!(bot.hits > 0);
+		assert!(bot.nodes - nodes < nodes);
+	}
+
+	#[test]
+	fn expectimax_clear_now_test() {
+		let board: Board = "
+			##########
+			#........#
+			#......i.#
+			#......i.#
+			#......i.#
+			#......i.#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#.OOOOOOO#
+			##########
+		".parse().unwrap();
+		assert_eq!('O', board.collector.get_next().letter());
+
+		// Only lines count, and the O coming next cannot clear: the line the I clears now must reach the leaf
+		let dna: Dna = [("lines".to_string(), 1.0)].iter().cloned().collect();
+		let mut bot = Expectimax::new(&dna, 2);
+		let piece = bot.ask(&board).unwrap();
+		assert_eq!(1, search::simulate(&board, piece).1);
+		assert_eq!(Some(1.0), bot.evaluation());
+	}
+}
@@ -0,0 +1,152 @@
+
+use super::super::model::board::Board;
+use super::search;
+
+/// Something the heuristic bot measures on the board left by a placement
+pub struct Feature {
+	pub name: &'static str,
+	/// Value for the board after the placement and the lines it cleared
+	pub eval: fn(&Board, usize) -> f64
+}
+
+/// Every feature a genome can weight. Adding one here is enough for the
+/// bots, the trainer and the genome files to pick it up.
+pub const FEATURES: &[Feature] = &[
+	Feature { name: "height", eval: height },
+	Feature { name: "max_height", eval: max_height },
+	Feature { name: "lines", eval: lines },
+	Feature { name: "holes", eval: holes },
+	Feature { name: "bumpiness", eval: bumpiness },
+	Feature { name: "wells", eval: wells },
+	Feature { name: "row_transitions", eval: row_transitions },
+	Feature { name: "column_transitions", eval: column_transitions },
+	Feature { name: "t_slots", eval: t_slots },
+];
+
+pub fn find(name: &str) -> Option<&'static Feature> {
+	FEATURES.iter().find(|feature| feature.name == name)
+}
+
+fn is_filled(board: &Board, i: usize, j: usize) -> bool {
+	board.grid[(i, j)] != 0
+}
+
+/// Sum of the heights of the columns
+fn height(board: &Board, _: usize) -> f64 {
+	search::column_heights(board).iter().sum::<usize>() as f64
+}
+
+fn max_height(board: &Board, _: usize) -> f64 {
+	search::column_heights(board).iter().cloned().max().unwrap_or(0) as f64
+}
+
+fn lines(_: &Board, lines: usize) -> f64 {
+	lines as f64
+}
+
+fn holes(board: &Board, _: usize) -> f64 {
+	search::holes(board) as f64
+}
+
+fn bumpiness(board: &Board, _: usize) -> f64 {
+	search::bumpiness(&search::column_heights(board)) as f64
+}
+
+/// Sum of the depths of the columns lower than both neighbours (walls are high)
+fn wells(board: &Board, _: usize) -> f64 {
+	let heights = search::column_heights(board);
+	(0..heights.len())
+		.map(|j| {
+			let left = if j == 0 { usize::MAX } else { heights[j - 1] };
+			let right = if j + 1 == heights.len() { usize::MAX } else { heights[j + 1] };
+			left.min(right).saturating_sub(heights[j]).min(18)
+		})
+		.sum::<usize>() as f64
+}
+
+/// Changes between filled and empty cells along the rows, walls included
+fn row_transitions(board: &Board, _: usize) -> f64 {
+	(2..20)
+		.map(|i| (1..10).filter(|j| is_filled(board, i, *j) != is_filled(board, i, j + 1)).count())
+		.sum::<usize>() as f64
+}
+
+/// Changes between filled and empty cells along the columns, floor included
+fn column_transitions(board: &Board, _: usize) -> f64 {
+	(2..10)
+		.map(|j| (2..20).filter(|i| is_filled(board, *i, j) != is_filled(board, i + 1, j)).count())
+		.sum::<usize>() as f64
+}
+
+/// Spots where a T pointing down fits with three of its four corners filled
+fn t_slots(board: &Board, _: usize) -> f64 {
+	let mut slots = 0;
+	for i in 2..19 {
+		for j in 2..10 {
+			let free = [(i, j - 1), (i, j), (i, j + 1), (i + 1, j)].iter()
+				.all(|(i, j)| !is_filled(board, *i, *j));
+			let corners = [(i - 1, j - 1), (i - 1, j + 1), (i + 1, j - 1), (i + 1, j + 1)].iter()
+				.filter(|(i, j)| is_filled(board, *i, *j))
+				.count();
+			if free && corners >= 3 {
+				slots += 1;
+			}
+		}
+	}
+	slots as f64
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	const BOARD: &str = "
+		##########
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#OO......#
+		#O...IJJJ#
+		#OO.IIIIJ#
+		##########
+	";
+
+	fn eval(name: &str, board: &Board) -> f64 {
+		(find(name).unwrap().eval)(board, 0)
+	}
+
+	#[test]
+	fn features_registry_test() {
+		assert!(find("holes").is_some());
+		assert!(find("spins").is_none());
+		let mut names: Vec<&str> = FEATURES.iter().map(|f| f.name).collect();
+		names.sort();
+		names.dedup();
+		assert_eq!(FEATURES.len(), names.len());
+	}
+
+	#[test]
+	fn features_eval_test() {
+		let board: Board = BOARD.parse().unwrap();
+		assert_eq!(3.0, eval("max_height", &board));
+		assert_eq!(1.0, eval("holes", &board));
+		assert_eq!(1.0, eval("wells", &board));
+		assert_eq!(1.0, eval("t_slots", &board));
+		// 2 per row (the walls)
+		assert_eq!(36.0, eval("row_transitions", &board));
+		assert_eq!(10.0, eval("column_transitions", &board));
+	}
+}
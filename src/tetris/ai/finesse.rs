@@ -0,0 +1,107 @@
+
+use super::super::model::board::{Board, Input, Piece};
+use super::search;
+
+/// Moves and rotations used for a placement, and the fewest it needed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+	pub used: usize,
+	pub minimal: usize
+}
+
+impl Placement {
+
+	pub fn is_fault(&self) -> bool {
+		self.used > self.minimal
+	}
+}
+
+/// Counts the inputs of a human against the fewest reaching the same placements
+#[derive(Default)]
+pub struct Finesse {
+	/// Board when the current piece spawned
+	spawned: Option<Board>,
+	/// Moves and rotations of the current piece so far
+	moves: usize,
+	pub last: Option<Placement>,
+	/// Placements of the session, the ones with extra inputs and the extra inputs
+	pub pieces: usize,
+	pub faults: usize,
+	pub extra: usize
+}
+
+impl Finesse {
+
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// A new piece spawned
+	pub fn observe(&mut self, board: &Board) {
+		self.spawned = Some(board.clone());
+		self.moves = 0;
+	}
+
+	/// Soft drops are free, only moves and rotations count
+	pub fn input(&mut self, input: Input) {
+		if input != Input::SoftDrop {
+			self.moves += 1;
+		}
+	}
+
+	/// Compares the inputs of the piece locked at `played` to the fewest needed
+	pub fn lock(&mut self, played: &Piece) -> Option<Placement> {
+
+		let board = self.spawned.take()?;
+		let placement = Placement {
+			used: self.moves,
+			minimal: search::finesse(&board, played)?
+		};
+
+		self.pieces += 1;
+		if placement.is_fault() {
+			self.faults += 1;
+			self.extra += placement.used - placement.minimal;
+		}
+		self.last = Some(placement);
+
+		Some(placement)
+	}
+
+	/// Share of the placements without extra inputs (1 if none yet)
+	pub fn accuracy(&self) -> f64 {
+		if self.pieces == 0 {
+			return 1.0;
+		}
+		(self.pieces - self.faults) as f64 / self.pieces as f64
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::super::model::board;
+
+	#[test]
+	fn finesse_lock_test() {
+		let board = Board::new([2; 16]);
+		let mut finesse = Finesse::new();
+		let drop = |piece: Piece| (0..20).fold(piece, |piece, _| board::down(&board, &piece).unwrap_or(piece));
+
+		// Left then right again: two moves for nothing
+		finesse.observe(&board);
+		for input in [Input::Left, Input::Right, Input::SoftDrop].iter() {
+			finesse.input(*input);
+		}
+		assert_eq!(Some(Placement { used: 2, minimal: 0 }), finesse.lock(&drop(board.current)));
+
+		finesse.observe(&board);
+		finesse.input(Input::Left);
+		let left = board::left(&board, &board.current).ok().unwrap();
+		assert!(!finesse.lock(&drop(left)).unwrap().is_fault());
+
+		assert_eq!((2, 1, 2), (finesse.pieces, finesse.faults, finesse.extra));
+		assert_eq!(0.5, finesse.accuracy());
+	}
+}
@@ -0,0 +1,136 @@
+extern crate serde;
+extern crate serde_json;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use super::bot::Dna;
+use super::features;
+
+/// Rules the genomes are trained for: our 8 x 18 playfield without hold
+pub const RULESET: &str = "tetris-rs 8x18";
+
+/// Weights of the heuristic bot with what is known about them, saved as JSON
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Genome {
+	/// Weight of each feature, by name
+	pub weights: Dna,
+	/// Lines cleared per game while training
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub fitness: Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub generation: Option<usize>,
+	pub ruleset: String
+}
+
+/// A file holds a genome or a list of them
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum GenomeFile {
+	One(Genome),
+	Many(Vec<Genome>)
+}
+
+impl Genome {
+
+	pub fn new(dna: Dna) -> Self {
+		Self {
+			weights: dna,
+			fitness: None,
+			generation: None,
+			ruleset: RULESET.into()
+		}
+	}
+
+	/// Weights for the bot. Every feature must be in the registry.
+	pub fn dna(&self) -> io::Result<Dna> {
+		match self.weights.keys().find(|name| features::find(name).is_none()) {
+			Some(unknown) => Err(invalid(&format!("unknown feature `{}`", unknown))),
+			None => Ok(self.weights.clone())
+		}
+	}
+
+	/// Genomes of a file (a single genome or a list)
+	pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Self>> {
+		let genomes = match serde_json::from_str(&fs::read_to_string(path)?)? {
+			GenomeFile::One(genome) => vec![genome],
+			GenomeFile::Many(genomes) => genomes
+		};
+		for genome in genomes.iter().filter(|g| g.ruleset != RULESET) {
+			leg::warn(&format!("Genome trained for `{}`", genome.ruleset), None, None);
+		}
+		Ok(genomes)
+	}
+
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		fs::write(path, serde_json::to_string_pretty(self)?)
+	}
+}
+
+/// File of the genome of a generation: `best.json` becomes `best-gen3.json`
+pub fn generation_path<P: AsRef<Path>>(path: P, generation: usize) -> PathBuf {
+	let path = path.as_ref();
+	let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+	let name = match path.extension() {
+		Some(extension) => format!("{}-gen{}.{}", stem, generation, extension.to_string_lossy()),
+		None => format!("{}-gen{}", stem, generation)
+	};
+	path.with_file_name(name)
+}
+
+fn invalid(reason: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::bot::default_dna;
+
+	#[test]
+	fn genome_round_trip_test() {
+
+		let mut genome = Genome::new(default_dna());
+		genome.fitness = Some(12.5);
+		genome.generation = Some(3);
+
+		let path = std::env::temp_dir().join("tetris_genome_test.json");
+		genome.save(&path).unwrap();
+		let genomes = Genome::load(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		assert_eq!(vec![genome], genomes);
+		assert_eq!(default_dna(), genomes[0].dna().unwrap());
+
+		assert_eq!(Path::new("out/best-gen3.json"), generation_path("out/best.json", 3));
+		assert_eq!(Path::new("best-gen0"), generation_path("best", 0));
+	}
+
+	#[test]
+	fn genome_list_test() {
+
+		let path = std::env::temp_dir().join("tetris_genome_list_test.json");
+		fs::write(&path, r#"[
+			{ "weights": { "height": -1 }, "ruleset": "tetris-rs 8x18" },
+			{ "weights": { "holes": -1, "t_slots": 0.5 }, "ruleset": "tetris-rs 8x18" }
+		]"#).unwrap();
+		let genomes = Genome::load(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		assert_eq!(2, genomes.len());
+		assert_eq!(Some(&0.5), genomes[1].dna().unwrap().get("t_slots"));
+	}
+
+	#[test]
+	fn genome_features_test() {
+
+		let mut genome = Genome::new(default_dna());
+		genome.weights.insert("wells".into(), 1.0);
+		assert!(genome.dna().is_ok());
+
+		genome.weights.insert("spins".into(), 1.0);
+		assert!(genome.dna().is_err());
+	}
+}
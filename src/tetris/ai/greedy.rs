@@ -0,0 +1,22 @@
+
+use super::super::model::board::{Board, Piece};
+use super::controller::Controller;
+use super::search;
+
+/// Clears as many lines as possible right now, then keeps the stack low and without holes
+pub struct GreedyBot;
+
+impl Controller for GreedyBot {
+
+	fn ask(&mut self, board: &Board) -> Option<Piece> {
+		search::placements(board)
+			.into_iter()
+			.map(|piece| {
+				let (next, lines) = search::simulate(board, piece);
+				let height: usize = search::column_heights(&next).iter().sum();
+				((lines, -(search::holes(&next) as i64), -(height as i64)), piece)
+			})
+			.max_by_key(|(key, _)| *key)
+			.map(|(_, piece)| piece)
+	}
+}
@@ -0,0 +1,241 @@
+
+use rand::{rngs, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+use super::super::model::board::{Board, Piece};
+use super::super::model::shape::Shape;
+use super::bot::{Bot, Dna};
+use super::controller::Controller;
+use super::search;
+
+/// Reward of a game that topped out
+const TOP_OUT: f64 = -100.0;
+
+/// Exploration constant of UCT
+const EXPLORATION: f64 = 1.41;
+
+/// Pieces known when asked (the current one and the preview): the tree
+/// branches on their placements, rollouts take over after them
+const KNOWN: usize = 2;
+
+/// How the rollouts place their pieces
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rollout {
+	Random,
+	/// Best placement for the heuristic bot
+	Heuristic
+}
+
+#[derive(Clone)]
+pub struct MctsConfig {
+	/// Iterations per piece
+	pub iterations: usize,
+	/// Stops earlier when thinking longer than this per piece
+	pub time_limit: Option<Duration>,
+	pub rollout: Rollout,
+	/// Random pieces placed by a rollout
+	pub rollout_depth: usize,
+	/// Scores the boards where rollouts end
+	pub dna: Dna
+}
+
+struct Node {
+	/// Placement leading here, and the board it leaves (the next piece spawned)
+	piece: Option<Piece>,
+	board: Board,
+	/// Lines cleared from the root
+	lines: usize,
+	depth: usize,
+	children: Vec<usize>,
+	unexpanded: Vec<Piece>,
+	visits: usize,
+	reward: f64
+}
+
+impl Node {
+
+	fn new(piece: Option<Piece>, board: Board, lines: usize, depth: usize) -> Self {
+		let unexpanded = if depth < KNOWN && !board.is_gameover() { search::placements(&board) } else { vec![] };
+		Self { piece, board, lines, depth, children: vec![], unexpanded, visits: 0, reward: 0.0 }
+	}
+}
+
+/// Monte Carlo tree search over the placements of the known pieces
+pub struct Mcts {
+	config: MctsConfig,
+	bot: Bot,
+	rng: rngs::SmallRng,
+	/// Iterations run so far
+	pub iterations: usize
+}
+
+impl Mcts {
+
+	pub fn new(config: MctsConfig, seed: [u8; 16]) -> Self {
+		Self {
+			bot: Bot::new(&config.dna),
+			rng: rngs::SmallRng::from_seed(seed),
+			config,
+			iterations: 0
+		}
+	}
+
+	/// Board after locking `piece`, with `shape` as the new current piece
+	fn child(board: &Board, piece: Piece, shape: Shape) -> (Board, usize) {
+		let (mut next, lines) = search::simulate(board, piece);
+		next.current = next.spawn(shape);
+		(next, lines)
+	}
+
+	fn random_shape(&mut self) -> Shape {
+		Shape::from_index(self.rng.gen_range(1, 8)).unwrap()
+	}
+
+	/// Plays random pieces from the board and scores where it ends
+	fn rollout(&mut self, board: &Board, mut lines: usize) -> f64 {
+
+		let mut board = board.clone();
+
+		for _ in 0..self.config.rollout_depth {
+			if board.is_gameover() {
+				return TOP_OUT;
+			}
+			let piece = match self.config.rollout {
+				Rollout::Heuristic => self.bot.ask(&board),
+				Rollout::Random => {
+					let placements = search::placements(&board);
+					if placements.is_empty() { None } else { Some(placements[self.rng.gen_range(0, placements.len())]) }
+				}
+			};
+			match piece {
+				Some(piece) => {
+					let shape = self.random_shape();
+					let (next, cleared) = Self::child(&board, piece, shape);
+					board = next;
+					lines += cleared;
+				}
+				None => return TOP_OUT
+			}
+		}
+
+		if board.is_gameover() { TOP_OUT } else { self.bot.calc_score(&board, lines) }
+	}
+
+	/// Child with the best upper confidence bound, rewards scaled to the range seen
+	fn select(nodes: &[Node], parent: usize, (min, max): (f64, f64)) -> usize {
+		let range = if max > min { max - min } else { 1.0 };
+		let ln_visits = (nodes[parent].visits.max(1) as f64).ln();
+		let uct = |index: &usize| {
+			let node = &nodes[*index];
+			let mean = (node.reward / node.visits as f64 - min) / range;
+			mean + EXPLORATION * (ln_visits / node.visits as f64).sqrt()
+		};
+		*nodes[parent].children.iter()
+			.max_by(|a, b| uct(a).partial_cmp(&uct(b)).unwrap())
+			.unwrap()
+	}
+}
+
+impl Controller for Mcts {
+
+	/// Most visited placement of the current piece
+	fn ask(&mut self, board: &Board) -> Option<Piece> {
+
+		let start = Instant::now();
+		let mut nodes = vec![Node::new(None, board.clone(), 0, 0)];
+		let mut bounds = (f64::MAX, f64::MIN);
+
+		for iteration in 0..self.config.iterations.max(1) {
+
+			if iteration > 0 && self.config.time_limit.is_some_and(|limit| start.elapsed() >= limit) {
+				break;
+			}
+			self.iterations += 1;
+
+			// Selection
+			let mut path = vec![0];
+			let mut current = 0;
+			while nodes[current].unexpanded.is_empty() && !nodes[current].children.is_empty() {
+				current = Self::select(&nodes, current, bounds);
+				path.push(current);
+			}
+
+			// Expansion
+			if let Some(piece) = nodes[current].unexpanded.pop() {
+				let shape = if nodes[current].depth + 1 < KNOWN { board.collector.get_next() } else { self.random_shape() };
+				let (next, lines) = Self::child(&nodes[current].board, piece, shape);
+				let node = Node::new(Some(piece), next, nodes[current].lines + lines, nodes[current].depth + 1);
+				nodes.push(node);
+				let index = nodes.len() - 1;
+				nodes[current].children.push(index);
+				path.push(index);
+				current = index;
+			}
+
+			// Simulation
+			let board = nodes[current].board.clone();
+			let reward = self.rollout(&board, nodes[current].lines);
+			bounds = (bounds.0.min(reward), bounds.1.max(reward));
+
+			// Backpropagation
+			for index in path {
+				nodes[index].visits += 1;
+				nodes[index].reward += reward;
+			}
+		}
+
+		nodes[0].children.iter()
+			.max_by_key(|index| nodes[**index].visits)
+			.and_then(|index| nodes[*index].piece)
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::bot::default_dna;
+
+	const BOARD: &str = "
+		##########
+		#........#
+		#...i....#
+		#...i....#
+		#...i....#
+		#...i....#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#OOSSZZJ.#
+		#OOSZZJJ.#
+		#OSSLLLJ.#
+		##########
+	";
+
+	fn config(iterations: usize, time_limit: Option<Duration>) -> MctsConfig {
+		MctsConfig { iterations, time_limit, rollout: Rollout::Random, rollout_depth: 1, dna: default_dna() }
+	}
+
+	#[test]
+	fn mcts_clear_lines_test() {
+		let board: Board = BOARD.parse().unwrap();
+		let mut bot = Mcts::new(config(300, None), [5; 16]);
+		let piece = bot.ask(&board).unwrap();
+		assert_eq!(300, bot.iterations);
+		assert_eq!(3, search::simulate(&board, piece).1);
+	}
+
+	#[test]
+	fn mcts_time_limit_test() {
+		let board: Board = BOARD.parse().unwrap();
+		let mut bot = Mcts::new(config(1_000_000, Some(Duration::from_millis(0))), [5; 16]);
+		assert!(bot.ask(&board).is_some());
+		assert_eq!(1, bot.iterations);
+	}
+}
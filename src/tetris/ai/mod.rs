@@ -1,2 +1,17 @@
-
+pub mod bench;
 pub mod bot;
+pub mod coach;
+pub mod controller;
+pub mod expectimax;
+pub mod features;
+pub mod finesse;
+pub mod genome;
+pub mod greedy;
+pub mod mcts;
+pub mod network;
+pub mod random;
+pub mod replay;
+pub mod search;
+pub mod tbp;
+pub mod tournament;
+pub mod train;
@@ -0,0 +1,232 @@
+extern crate serde;
+extern crate serde_json;
+
+use rand::{rngs, Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use super::super::model::board::{Board, Piece};
+use super::super::model::shape::Shape;
+use super::controller::Controller;
+use super::search;
+use super::train;
+
+/// Column heights, holes per column, lines cleared and the next shape (one hot)
+pub const INPUTS: usize = 8 + 8 + 1 + 7;
+
+/// What the network sees of the board left by a placement
+pub fn inputs(board: &Board, lines: usize, next: Shape) -> Vec<f64> {
+
+	let mut inputs: Vec<f64> = search::column_heights(board).iter()
+		.map(|h| *h as f64 / 18.0)
+		.collect();
+
+	inputs.extend((2..10).map(|j| {
+		(2..20)
+			.skip_while(|i| board.grid[(*i, j)] == 0)
+			.filter(|i| board.grid[(*i, j)] == 0)
+			.count() as f64 / 18.0
+	}));
+
+	inputs.push(lines as f64 / 4.0);
+	inputs.extend((1..8).map(|index| if next.index() == index { 1.0 } else { 0.0 }));
+
+	inputs
+}
+
+/// Perceptron with one hidden layer (tanh) scoring positions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Network {
+	pub hidden: usize,
+	/// Hidden layer weights, row by row (one row per hidden unit)
+	w1: Vec<f64>,
+	b1: Vec<f64>,
+	w2: Vec<f64>,
+	b2: f64
+}
+
+impl Network {
+
+	/// Random small weights
+	pub fn new(hidden: usize, seed: [u8; 16]) -> Self {
+		let mut rng = rngs::SmallRng::from_seed(seed);
+		let scale = 1.0 / (INPUTS as f64).sqrt();
+		Self {
+			hidden,
+			w1: (0..hidden * INPUTS).map(|_| rng.gen_range(-scale, scale)).collect(),
+			b1: vec![0.0; hidden],
+			w2: (0..hidden).map(|_| rng.gen_range(-scale, scale)).collect(),
+			b2: 0.0
+		}
+	}
+
+	fn activations(&self, inputs: &[f64]) -> Vec<f64> {
+		self.w1.chunks(INPUTS)
+			.zip(self.b1.iter())
+			.map(|(row, b)| (row.iter().zip(inputs).map(|(w, x)| w * x).sum::<f64>() + b).tanh())
+			.collect()
+	}
+
+	pub fn eval(&self, inputs: &[f64]) -> f64 {
+		self.activations(inputs).iter().zip(self.w2.iter()).map(|(h, w)| h * w).sum::<f64>() + self.b2
+	}
+
+	/// One step of gradient descent on the squared error. Returns the error before the step.
+	pub fn learn(&mut self, inputs: &[f64], target: f64, rate: f64) -> f64 {
+
+		let hidden = self.activations(inputs);
+		let error = hidden.iter().zip(self.w2.iter()).map(|(h, w)| h * w).sum::<f64>() + self.b2 - target;
+
+		for (j, h) in hidden.iter().enumerate() {
+			let delta = error * self.w2[j] * (1.0 - h * h);
+			self.w2[j] -= rate * error * h;
+			self.b1[j] -= rate * delta;
+			for (w, x) in self.w1[j * INPUTS..(j + 1) * INPUTS].iter_mut().zip(inputs) {
+				*w -= rate * delta * x;
+			}
+		}
+		self.b2 -= rate * error;
+
+		error * error
+	}
+
+	/// Epochs of stochastic gradient descent over the samples. Returns the mean error of the last one.
+	pub fn fit(&mut self, samples: &mut [(Vec<f64>, f64)], epochs: usize, rate: f64, seed: [u8; 16]) -> f64 {
+		let mut rng = rngs::SmallRng::from_seed(seed);
+		let mut loss = 0.0;
+		for _ in 0..epochs {
+			samples.shuffle(&mut rng);
+			loss = samples.iter().map(|(inputs, target)| self.learn(inputs, *target, rate)).sum::<f64>()
+				/ samples.len().max(1) as f64;
+		}
+		loss
+	}
+
+	pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let network: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+		if network.w1.len() != network.hidden * INPUTS || network.b1.len() != network.hidden || network.w2.len() != network.hidden {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "the layers do not match the inputs"));
+		}
+		Ok(network)
+	}
+
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		fs::write(path, serde_json::to_string_pretty(self)?)
+	}
+}
+
+/// Placements of a game played by `controller`, each with the lines cleared
+/// in the `horizon` placements starting with it (minus a point per
+/// placement missed by topping out before the horizon)
+pub fn samples(controller: &mut dyn Controller, seed: [u8; 16], max_pieces: usize, horizon: usize) -> Vec<(Vec<f64>, f64)> {
+
+	let mut inputs = vec![];
+	let mut lines = vec![];
+
+	let outcome = train::play_observed(controller, seed, max_pieces, &mut |board, piece, cleared| {
+		let (next, _) = search::simulate(board, *piece);
+		inputs.push(self::inputs(&next, cleared, board.collector.get_next()));
+		lines.push(cleared);
+	});
+	let topped_out = outcome.pieces < max_pieces;
+
+	inputs.into_iter()
+		.enumerate()
+		.map(|(i, inputs)| {
+			let end = (i + horizon).min(lines.len());
+			let missed = if topped_out { i + horizon - end } else { 0 };
+			let target = lines[i..end].iter().sum::<usize>() as f64 - missed as f64;
+			(inputs, target / horizon.max(1) as f64)
+		})
+		.collect()
+}
+
+/// Plays the placement the network likes best
+pub struct NetworkBot {
+	network: Network
+}
+
+impl NetworkBot {
+
+	pub fn new(network: Network) -> Self {
+		Self {
+			network
+		}
+	}
+}
+
+impl Controller for NetworkBot {
+
+	fn ask(&mut self, board: &Board) -> Option<Piece> {
+
+		let next = board.collector.get_next();
+		let mut best: Option<(f64, Piece)> = None;
+
+		for piece in search::placements(board) {
+			let (after, lines) = search::simulate(board, piece);
+			let score = self.network.eval(&inputs(&after, lines, next));
+			if best.is_none_or(|(best_score, _)| score > best_score) {
+				best = Some((score, piece));
+			}
+		}
+
+		best.map(|(_, piece)| piece)
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::bot::{Bot, default_dna};
+	use super::super::super::model::board;
+
+	#[test]
+	fn network_inputs_test() {
+		let board = Board::new([0; 16]);
+		let inputs = inputs(&board, 2, Shape::T(0));
+		assert_eq!(INPUTS, inputs.len());
+		assert_eq!(0.5, inputs[16]);
+		assert_eq!(1.0, inputs[16 + Shape::T(0).index() as usize]);
+	}
+
+	#[test]
+	fn network_fit_test() {
+		let mut samples: Vec<(Vec<f64>, f64)> = (0..20)
+			.map(|i| {
+				let mut inputs = vec![0.0; INPUTS];
+				inputs[i % INPUTS] = 1.0;
+				(inputs, if i % 2 == 0 { 0.5 } else { -0.5 })
+			})
+			.collect();
+		let mut network = Network::new(8, [1; 16]);
+		let before = network.fit(&mut samples, 1, 0.1, [2; 16]);
+		let after = network.fit(&mut samples, 200, 0.1, [2; 16]);
+		assert!(after < before / 10.0);
+	}
+
+	#[test]
+	fn network_save_test() {
+		let network = Network::new(4, [3; 16]);
+		let path = std::env::temp_dir().join("tetris_network_test.json");
+		network.save(&path).unwrap();
+		let loaded = Network::load(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+		let inputs = vec![0.5; INPUTS];
+		assert_eq!(network.hidden, loaded.hidden);
+		assert!((network.eval(&inputs) - loaded.eval(&inputs)).abs() < 1e-9);
+	}
+
+	#[test]
+	fn network_bot_test() {
+		let samples = samples(&mut Bot::new(&default_dna()), [4; 16], 20, 5);
+		assert_eq!(20, samples.len());
+		assert!(samples.iter().all(|(inputs, _)| inputs.len() == INPUTS));
+
+		let board = Board::new([4; 16]);
+		let piece = NetworkBot::new(Network::new(4, [3; 16])).ask(&board).unwrap();
+		assert!(!board::collides(&board, &piece) && board::down(&board, &piece).is_err());
+	}
+}
@@ -0,0 +1,34 @@
+
+use rand::{rngs, Rng, SeedableRng};
+use super::super::model::board::{Board, Piece};
+use super::controller::Controller;
+use super::search;
+
+/// Places every piece anywhere it can go. The baseline for any other strategy.
+pub struct RandomBot {
+	rng: rngs::SmallRng
+}
+
+impl RandomBot {
+
+	pub fn new(seed: [u8; 16]) -> Self {
+		Self {
+			rng: rngs::SmallRng::from_seed(seed)
+		}
+	}
+}
+
+impl Controller for RandomBot {
+
+	fn ask(&mut self, board: &Board) -> Option<Piece> {
+
+		let placements = search::placements(board);
+
+		if placements.is_empty() {
+			None
+		}
+		else {
+			Some(placements[self.rng.gen_range(0, placements.len())])
+		}
+	}
+}
@@ -0,0 +1,36 @@
+
+use std::collections::VecDeque;
+use super::super::model::board::{Board, Piece};
+use super::controller::Controller;
+
+/// Plays back a list of placements (e.g. the pages of a fumen), one per piece
+pub struct Replay {
+	placements: VecDeque<Piece>
+}
+
+impl Replay {
+
+	pub fn new(placements: Vec<Piece>) -> Self {
+		Self {
+			placements: placements.into()
+		}
+	}
+}
+
+impl Controller for Replay {
+
+	/// Next placement of the recording. Gives up when it is over or the pieces do not match.
+	fn ask(&mut self, board: &Board) -> Option<Piece> {
+
+		let piece = self.placements.pop_front()?;
+
+		if piece.shape.letter() == board.current.shape.letter() {
+			Some(piece)
+		}
+		else {
+			leg::warn("The replay does not match the pieces of the game", "Replay".into(), None);
+			self.placements.clear();
+			None
+		}
+	}
+}
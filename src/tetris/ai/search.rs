@@ -0,0 +1,258 @@
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use super::super::model::board::{self, Board, Input, Piece};
+
+const INPUTS: [Input; 4] = [Input::Rotate, Input::Left, Input::Right, Input::SoftDrop];
+
+/// Inputs of a human, who can also rotate the other way and twice at once
+const HUMAN_INPUTS: [Input; 6] = [Input::Rotate, Input::RotateCcw, Input::Rotate180, Input::Left, Input::Right, Input::SoftDrop];
+
+/// Position reached while exploring, with the input that led to it
+struct Node {
+	piece: Piece,
+	parent: Option<(usize, Input)>
+}
+
+/// Explores every position of the current piece reachable with legal inputs
+/// (breadth first, so the path to each one is the shortest)
+fn explore(board: &Board) -> Vec<Node> {
+
+	let mut nodes = vec![];
+
+	if board::collides(board, &board.current) {
+		return nodes;
+	}
+
+	let key = |piece: &Piece| (piece.shape.rotation(), piece.position.x, piece.position.y);
+	let mut visited = HashSet::new();
+	let mut queue = VecDeque::new();
+
+	visited.insert(key(&board.current));
+	nodes.push(Node { piece: board.current, parent: None });
+	queue.push_back(0);
+
+	while let Some(index) = queue.pop_front() {
+		for input in INPUTS.iter() {
+			if let Ok(next) = board::apply(board, &nodes[index].piece, *input) {
+				if visited.insert(key(&next)) {
+					nodes.push(Node { piece: next, parent: Some((index, *input)) });
+					queue.push_back(nodes.len() - 1);
+				}
+			}
+		}
+	}
+
+	nodes
+}
+
+fn is_resting(board: &Board, piece: &Piece) -> bool {
+	board::down(board, piece).is_err()
+}
+
+fn sorted_cells(piece: &Piece) -> Vec<(usize, usize)> {
+	let mut cells = piece.cells();
+	cells.sort();
+	cells
+}
+
+/// Every final position (resting on the stack) the current piece can reach
+/// with legal inputs (positions covering the same cells are only listed once)
+pub fn placements(board: &Board) -> Vec<Piece> {
+
+	let mut placements: Vec<Piece> = vec![];
+	let mut covered: HashSet<Vec<(usize, usize)>> = HashSet::new();
+
+	for node in explore(board) {
+		if is_resting(board, &node.piece) && covered.insert(sorted_cells(&node.piece)) {
+			placements.push(node.piece);
+		}
+	}
+
+	placements
+}
+
+/// Shortest sequence of inputs taking the current piece to the final position
+/// `target` (any position covering the same cells), or `None` if unreachable
+pub fn path(board: &Board, target: &Piece) -> Option<Vec<Input>> {
+
+	let cells = sorted_cells(target);
+	let nodes = explore(board);
+
+	let mut index = nodes.iter()
+		.position(|node| sorted_cells(&node.piece) == cells && is_resting(board, &node.piece))?;
+
+	let mut inputs = vec![];
+	while let Some((parent, input)) = nodes[index].parent {
+		inputs.push(input);
+		index = parent;
+	}
+	inputs.reverse();
+
+	Some(inputs)
+}
+
+/// Fewest moves and rotations taking the current piece to the final position
+/// `target`, soft drops being free as gravity brings the piece down anyway
+/// (`None` if unreachable)
+pub fn finesse(board: &Board, target: &Piece) -> Option<usize> {
+
+	if board::collides(board, &board.current) {
+		return None;
+	}
+
+	let cells = sorted_cells(target);
+	let key = |piece: &Piece| (piece.shape.rotation(), piece.position.x, piece.position.y);
+	let mut costs = HashMap::new();
+	let mut queue = VecDeque::new();
+
+	costs.insert(key(&board.current), 0);
+	queue.push_back((board.current, 0));
+
+	// Breadth first with free drops at the front of the queue, so positions come out by cost
+	while let Some((piece, cost)) = queue.pop_front() {
+
+		if costs[&key(&piece)] < cost {
+			continue;
+		}
+		if is_resting(board, &piece) && sorted_cells(&piece) == cells {
+			return Some(cost);
+		}
+
+		for input in HUMAN_INPUTS.iter() {
+			if let Ok(next) = board::apply(board, &piece, *input) {
+				let free = *input == Input::SoftDrop;
+				let next_cost = if free { cost } else { cost + 1 };
+				if costs.get(&key(&next)).is_none_or(|known| next_cost < *known) {
+					costs.insert(key(&next), next_cost);
+					if free { queue.push_front((next, next_cost)) } else { queue.push_back((next, next_cost)) }
+				}
+			}
+		}
+	}
+
+	None
+}
+
+/// Board after locking the current piece at `piece`, and the number of lines it cleared
+pub fn simulate(board: &Board, piece: Piece) -> (Board, usize) {
+	let mut next = board.clone();
+	next.current = piece;
+	next.place_current_piece();
+	let lines = next.remove_full_lines();
+	(next, lines)
+}
+
+// Features of a board
+
+/// Height of each column of the playfield
+pub fn column_heights(board: &Board) -> Vec<usize> {
+	(2..10)
+		.map(|j| {
+			(2..20)
+				.find(|i| board.grid[(*i, j)] != 0)
+				.map_or(0, |i| 20 - i)
+		})
+		.collect()
+}
+
+/// Empty cells with a block somewhere above them
+pub fn holes(board: &Board) -> usize {
+	(2..10)
+		.map(|j| {
+			(2..20)
+				.skip_while(|i| board.grid[(*i, j)] == 0)
+				.filter(|i| board.grid[(*i, j)] == 0)
+				.count()
+		})
+		.sum()
+}
+
+pub fn bumpiness(heights: &[usize]) -> usize {
+	heights.windows(2)
+		.map(|w| (w[0] as i64 - w[1] as i64).unsigned_abs() as usize)
+		.sum()
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	const BOARD: &str = "
+		##########
+		#...t....#
+		#..ttt...#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#O.O.....#
+		#OO.S.I..#
+		##########
+	";
+
+	#[test]
+	fn search_placements_test() {
+		let board: Board = BOARD.parse().unwrap();
+		let placements = placements(&board);
+		// 6 flat + 6 upside down + 7 + 7 vertical
+		assert_eq!(26, placements.len());
+		assert!(placements.iter().all(|p| !board::collides(&board, p) && board::down(&board, p).is_err()));
+	}
+
+	#[test]
+	fn search_path_test() {
+		let board: Board = BOARD.parse().unwrap();
+		for target in placements(&board) {
+			let inputs = path(&board, &target).unwrap();
+			let piece = inputs.iter()
+				.fold(board.current, |piece, input| board::apply(&board, &piece, *input).ok().unwrap());
+			assert_eq!(sorted_cells(&target), sorted_cells(&piece));
+		}
+		// Floating positions are not final
+		assert!(path(&board, &board.current).is_none());
+	}
+
+	#[test]
+	fn search_finesse_test() {
+		let board: Board = BOARD.parse().unwrap();
+		for target in placements(&board) {
+			let moves = path(&board, &target).unwrap().into_iter().filter(|i| *i != Input::SoftDrop).count();
+			assert!(finesse(&board, &target).unwrap() <= moves);
+		}
+		let drop = |piece: Piece| (0..20).fold(piece, |piece, _| board::down(&board, &piece).unwrap_or(piece));
+		assert_eq!(Some(0), finesse(&board, &drop(board.current)));
+		assert_eq!(Some(1), finesse(&board, &drop(board::left(&board, &board.current).ok().unwrap())));
+		let ccw = board::apply(&board, &board.current, Input::RotateCcw).ok().unwrap();
+		assert!(finesse(&board, &drop(ccw)).unwrap() <= 1);
+	}
+
+	#[test]
+	fn search_features_test() {
+		let board: Board = BOARD.parse().unwrap();
+		assert_eq!(vec![2, 1, 2, 1, 0, 1, 0, 0], column_heights(&board));
+		assert_eq!(1, holes(&board));
+		assert_eq!(6, bumpiness(&column_heights(&board)));
+	}
+
+	#[test]
+	fn search_simulate_test() {
+		let board: Board = BOARD.replace("#OO.S.I..#", "#OOOSI...#").parse().unwrap();
+		let piece = placements(&board).into_iter()
+			.find(|p| p.cells().contains(&(19, 9)) && p.cells().contains(&(19, 7)))
+			.unwrap();
+		let (next, lines) = simulate(&board, piece);
+		assert_eq!(1, lines);
+		assert_eq!(vec![1, 0, 1, 0, 0, 0, 1, 0], column_heights(&next));
+	}
+}
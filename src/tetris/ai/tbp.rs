@@ -0,0 +1,569 @@
+extern crate serde;
+extern crate serde_json;
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use super::controller::Controller;
+use super::super::model::board::{self, Board, Piece};
+use super::super::model::shape::Shape;
+
+// Tetris Bot Protocol
+//
+// JSON messages, one per line. Bots see a 10 x 40 board where our 8 x 18
+// playfield sits in the bottom left corner and the two rightmost columns are
+// garbage, so line clears work the same way on both sides.
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+	Rules,
+	Start(Start),
+	Stop,
+	Suggest,
+	Play {
+		#[serde(rename = "move")]
+		mv: Move
+	},
+	NewPiece {
+		piece: String
+	},
+	Quit
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+	Info {
+		name: String,
+		version: String,
+		author: String,
+		features: Vec<String>
+	},
+	Ready,
+	Error {
+		reason: String
+	},
+	Suggestion {
+		moves: Vec<Move>
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Start {
+	pub hold: Option<String>,
+	pub queue: Vec<String>,
+	pub combo: u32,
+	pub back_to_back: bool,
+	pub board: Vec<Vec<Option<String>>>
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Move {
+	pub location: Location,
+	pub spin: String
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Location {
+	#[serde(rename = "type")]
+	pub kind: String,
+	pub orientation: String,
+	pub x: i64,
+	pub y: i64
+}
+
+const ORIENTATIONS: [&str; 4] = ["north", "east", "south", "west"];
+
+impl Move {
+
+	pub fn new(piece: &Piece) -> Self {
+		let (shape, x, y) = piece.srs_location();
+		Self {
+			location: Location {
+				kind: shape.letter().to_string(),
+				orientation: ORIENTATIONS[shape.rotation() as usize].into(),
+				x,
+				y
+			},
+			spin: "none".into()
+		}
+	}
+
+	/// Piece at the location of the move, if it fits inside our playfield
+	pub fn piece(&self) -> Option<Piece> {
+		let shape = letter_to_shape(&self.location.kind)?;
+		let rotation = ORIENTATIONS.iter().position(|o| *o == self.location.orientation)?;
+		Piece::from_srs_location(shape.with_rotation(rotation as u8), self.location.x, self.location.y)
+	}
+}
+
+fn letter_to_shape(letter: &str) -> Option<Shape> {
+	let mut chars = letter.chars();
+	match (chars.next(), chars.next()) {
+		(Some(c), None) if c.is_ascii_uppercase() => Shape::from_letter(c),
+		_ => None
+	}
+}
+
+impl Start {
+
+	pub fn new(board: &Board) -> Self {
+
+		let mut rows = vec![];
+
+		for y in 0..40 {
+			let row = (0..10)
+				.map(|x| {
+					if x >= 8 {
+						Some("G".to_string())
+					}
+					else if y >= 18 {
+						None
+					}
+					else {
+						match board.grid[(19 - y, x + 2)] {
+							0 => None,
+							cell => Some(Shape::from_index(cell).map_or('G', |s| s.letter()).to_string())
+						}
+					}
+				})
+				.collect();
+			rows.push(row);
+		}
+
+		Self {
+			hold: None,
+			queue: vec![
+				board.current.shape.letter().to_string(),
+				board.collector.get_next().letter().to_string()
+			],
+			combo: 0,
+			back_to_back: false,
+			board: rows
+		}
+	}
+
+	/// Board described by the message (the current piece is the first of the queue)
+	pub fn board(&self) -> Board {
+
+		let mut board = Board::new([0; 16]);
+
+		for (y, row) in self.board.iter().enumerate().take(18) {
+			for (x, cell) in row.iter().enumerate().take(8) {
+				board.grid[(19 - y, x + 2)] = match cell {
+					None => 0,
+					Some(letter) => letter_to_shape(letter).map_or(8, |s| s.index())
+				};
+			}
+		}
+
+		board
+	}
+}
+
+// Struct: TbpBot
+//
+// Frontend side: an external engine plays the board through the protocol.
+
+pub struct TbpBot {
+	input: Box<dyn BufRead + Send>,
+	output: Box<dyn Write + Send>,
+	child: Option<Child>,
+	name: String,
+	/// Grid and current shape we expect to see if the last move was played as suggested
+	expected: Option<(Board, Shape)>
+}
+
+impl TbpBot {
+
+	/// Launches the engine (a command line) and completes the handshake
+	pub fn spawn(command: &str) -> io::Result<Self> {
+
+		let mut parts = command.split_whitespace();
+		let program = parts.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+
+		let mut child = Command::new(program)
+			.args(parts)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.spawn()?;
+
+		let input = BufReader::new(child.stdout.take().unwrap());
+		let output = child.stdin.take().unwrap();
+
+		let mut bot = Self::new(Box::new(input), Box::new(output))?;
+		bot.child = Some(child);
+		Ok(bot)
+	}
+
+	/// Completes the handshake with an engine over the streams given
+	pub fn new(input: Box<dyn BufRead + Send>, output: Box<dyn Write + Send>) -> io::Result<Self> {
+
+		let mut bot = Self {
+			input,
+			output,
+			child: None,
+			name: String::new(),
+			expected: None
+		};
+
+		match bot.receive()? {
+			BotMessage::Info { name, version, author, .. } => {
+				leg::info(&format!("{} {} by {}", name, version, author), "TBP".into(), None);
+				bot.name = name;
+			},
+			_ => return Err(protocol_error("expected info"))
+		}
+
+		bot.send(&FrontendMessage::Rules)?;
+
+		match bot.receive()? {
+			BotMessage::Ready => Ok(bot),
+			BotMessage::Error { reason } => Err(protocol_error(&reason)),
+			_ => Err(protocol_error("expected ready"))
+		}
+	}
+
+	fn try_ask(&mut self, board: &Board) -> io::Result<Option<Piece>> {
+
+		// Keep the engine in sync: announce the new preview, or restart it if the board diverged
+		let in_sync = match &self.expected {
+			Some((expected, shape)) => expected.grid == board.grid && *shape == board.current.shape,
+			None => false
+		};
+
+		if in_sync {
+			let piece = board.collector.get_next().letter().to_string();
+			self.send(&FrontendMessage::NewPiece { piece })?;
+		}
+		else {
+			if self.expected.is_some() {
+				self.send(&FrontendMessage::Stop)?;
+			}
+			self.send(&FrontendMessage::Start(Start::new(board)))?;
+		}
+
+		self.send(&FrontendMessage::Suggest)?;
+
+		let moves = match self.receive()? {
+			BotMessage::Suggestion { moves } => moves,
+			_ => return Err(protocol_error("expected suggestion"))
+		};
+
+		let choice = moves.into_iter().find_map(|mv| {
+			let piece = mv.piece()?;
+			let legal = piece.shape.letter() == board.current.shape.letter()
+				&& !board::collides(board, &piece)
+				&& board::down(board, &piece).is_err();
+			if legal { Some((mv, piece)) } else { None }
+		});
+
+		match choice {
+			Some((mv, piece)) => {
+				self.send(&FrontendMessage::Play { mv })?;
+
+				let mut expected = board.clone();
+				expected.current = piece;
+				expected.place_current_piece();
+				expected.remove_full_lines();
+				self.expected = Some((expected, board.collector.get_next()));
+
+				Ok(Some(piece))
+			},
+			None => {
+				self.send(&FrontendMessage::Stop)?;
+				self.expected = None;
+				Ok(None)
+			}
+		}
+	}
+
+	fn send(&mut self, message: &FrontendMessage) -> io::Result<()> {
+		serde_json::to_writer(&mut self.output, message)?;
+		writeln!(self.output)?;
+		self.output.flush()
+	}
+
+	fn receive(&mut self) -> io::Result<BotMessage> {
+		receive(&mut self.input)
+	}
+}
+
+impl Controller for TbpBot {
+
+	/// Asks the engine where to place the current piece and plays its first legal suggestion
+	fn ask(&mut self, board: &Board) -> Option<Piece> {
+		match self.try_ask(board) {
+			Ok(piece) => piece,
+			Err(e) => {
+				leg::error(&format!("{}: {}", self.name, e), "TBP".into(), None);
+				self.expected = None;
+				None
+			}
+		}
+	}
+}
+
+/// Time an engine gets to exit once told to quit, before it is killed
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+impl Drop for TbpBot {
+	fn drop(&mut self) {
+		let _ = self.send(&FrontendMessage::Quit);
+		if let Some(child) = &mut self.child {
+			let start = Instant::now();
+			while let Ok(None) = child.try_wait() {
+				if start.elapsed() >= QUIT_TIMEOUT {
+					let _ = child.kill();
+					let _ = child.wait();
+					break;
+				}
+				thread::sleep(Duration::from_millis(10));
+			}
+		}
+	}
+}
+
+/// Reads messages until one can be understood
+fn receive<M: serde::de::DeserializeOwned>(input: &mut dyn BufRead) -> io::Result<M> {
+	loop {
+		let mut line = String::new();
+		if input.read_line(&mut line)? == 0 {
+			return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "engine closed the connection"));
+		}
+		if let Ok(message) = serde_json::from_str(&line) {
+			return Ok(message);
+		}
+	}
+}
+
+fn protocol_error(reason: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+// Bot side: our own bot answers a frontend through the protocol
+
+fn send<W: Write>(output: &mut W, message: &BotMessage) -> io::Result<()> {
+	serde_json::to_writer(&mut *output, message)?;
+	writeln!(output)?;
+	output.flush()
+}
+
+/// Serves `controller` until the frontend quits or closes the connection
+pub fn serve<R: BufRead, W: Write>(mut controller: Box<dyn Controller>, mut input: R, mut output: W) -> io::Result<()> {
+
+	send(&mut output, &BotMessage::Info {
+		name: "tetris-rs".into(),
+		version: env!("CARGO_PKG_VERSION").into(),
+		author: "Mr.Robb".into(),
+		features: vec![]
+	})?;
+
+	let mut state: Option<(Board, VecDeque<Shape>)> = None;
+
+	loop {
+
+		let message = match receive::<FrontendMessage>(&mut input) {
+			Ok(message) => message,
+			Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+			Err(e) => return Err(e)
+		};
+
+		match message {
+			FrontendMessage::Rules => send(&mut output, &BotMessage::Ready)?,
+			FrontendMessage::Start(start) => {
+				let queue = start.queue.iter().filter_map(|p| letter_to_shape(p)).collect();
+				state = Some((start.board(), queue));
+			},
+			FrontendMessage::Stop => state = None,
+			FrontendMessage::Suggest => {
+				let mut moves = vec![];
+				if let Some((board, queue)) = &mut state {
+					if spawn(board, queue) {
+						if let Some(piece) = controller.ask(board) {
+							moves.push(Move::new(&piece));
+						}
+					}
+				}
+				send(&mut output, &BotMessage::Suggestion { moves })?;
+			},
+			FrontendMessage::Play { mv } => {
+				if let Some((board, queue)) = &mut state {
+					if let Some(piece) = mv.piece() {
+						board.current = piece;
+						board.place_current_piece();
+						board.remove_full_lines();
+						queue.pop_front();
+					}
+				}
+			},
+			FrontendMessage::NewPiece { piece } => {
+				if let (Some((_, queue)), Some(shape)) = (&mut state, letter_to_shape(&piece)) {
+					queue.push_back(shape);
+				}
+			},
+			FrontendMessage::Quit => return Ok(())
+		}
+	}
+}
+
+/// Puts the first piece of the queue on the board. Fails if the queue is empty.
+fn spawn(board: &mut Board, queue: &VecDeque<Shape>) -> bool {
+
+	let shape = match queue.front() {
+		Some(shape) => *shape,
+		None => return false
+	};
+
+	board.set_current(Piece::new(board.grid.ncols() / 2 - shape.w() / 2, shape));
+	if let Some(next) = queue.get(1) {
+		board.collector.set_next(*next);
+	}
+
+	true
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::bot::{Bot, default_dna};
+	use std::thread;
+
+	fn connect<F>(engine: F) -> (TbpBot, thread::JoinHandle<()>)
+		where F: FnOnce(BufReader<io::PipeReader>, io::PipeWriter) + Send + 'static {
+		let (from_frontend, to_engine) = io::pipe().unwrap();
+		let (from_engine, to_frontend) = io::pipe().unwrap();
+		let handle = thread::spawn(move || engine(BufReader::new(from_frontend), to_frontend));
+		let bot = TbpBot::new(Box::new(BufReader::new(from_engine)), Box::new(to_engine)).unwrap();
+		(bot, handle)
+	}
+
+	fn play(bot: &mut TbpBot, board: &mut Board) -> Option<Piece> {
+		let piece = bot.ask(board)?;
+		board.current = piece;
+		board.place_current_piece();
+		board.remove_full_lines();
+		board.collector.next();
+		let shape = board.collector.get_current();
+		board.set_current(Piece::new(board.grid.ncols() / 2 - shape.w() / 2, shape));
+		Some(piece)
+	}
+
+	#[test]
+	fn tbp_message_test() {
+		let message: FrontendMessage = serde_json::from_str(r#"{"type":"rules","randomizer":"seven_bag"}"#).unwrap();
+		assert_eq!(FrontendMessage::Rules, message);
+
+		let mv = Move::new(&Piece { position: [2, 18].into(), shape: Shape::T(0) });
+		let json = serde_json::to_string(&FrontendMessage::Play { mv }).unwrap();
+		assert_eq!(r#"{"type":"play","move":{"location":{"type":"T","orientation":"north","x":1,"y":0},"spin":"none"}}"#, json);
+	}
+
+	#[test]
+	fn tbp_start_test() {
+		let board: Board = Board::new([3; 16]);
+		let start = Start::new(&board);
+		assert_eq!(40, start.board.len());
+		assert_eq!(Some("G".to_string()), start.board[0][9]);
+		assert_eq!(None, start.board[0][0]);
+		assert!(start.board().grid == board.grid);
+	}
+
+	#[test]
+	fn tbp_mock_engine_test() {
+
+		// Always suggests an I piece lying on the floor at the left, or nothing
+		let (mut bot, engine) = connect(|mut input, mut output| {
+			send(&mut output, &BotMessage::Info {
+				name: "mock".into(), version: "0".into(), author: "test".into(), features: vec![]
+			}).unwrap();
+			let mut log = vec![];
+			while let Ok(message) = receive::<FrontendMessage>(&mut input) {
+				match message {
+					FrontendMessage::Rules => send(&mut output, &BotMessage::Ready).unwrap(),
+					FrontendMessage::Suggest => {
+						let location = Location { kind: "I".into(), orientation: "north".into(), x: 1, y: 0 };
+						send(&mut output, &BotMessage::Suggestion {
+							moves: vec![Move { location, spin: "none".into() }]
+						}).unwrap();
+					},
+					FrontendMessage::Quit => break,
+					message => log.push(message)
+				}
+			}
+			assert!(matches!(&log[0], FrontendMessage::Start(_)));
+			assert!(matches!(&log[1], FrontendMessage::Play { .. }));
+			assert!(matches!(&log[2], FrontendMessage::NewPiece { .. }));
+			assert_eq!(FrontendMessage::Stop, log[3]);
+		});
+
+		assert_eq!("mock", bot.name);
+
+		let mut board: Board = "
+			##########
+			#........#
+			#..iiii..#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			##########
+		".parse().unwrap();
+		board.collector.set_next(Shape::I(0));
+
+		let piece = play(&mut bot, &mut board).unwrap();
+		assert_eq!(vec![(19, 2), (19, 3), (19, 4), (19, 5)], piece.cells());
+
+		// The spot is now taken, so the suggestion is rejected
+		assert!(play(&mut bot, &mut board).is_none());
+
+		drop(bot);
+		engine.join().unwrap();
+	}
+
+	#[test]
+	fn tbp_serve_test() {
+
+		let (mut bot, engine) = connect(|input, output| serve(Box::new(Bot::new(&default_dna())), input, output).unwrap());
+		let mut board = Board::new([7; 16]);
+
+		for _ in 0..5 {
+			let piece = play(&mut bot, &mut board).expect("the bot suggests a legal move");
+			assert!(piece.cells().iter().all(|(i, _)| *i <= 19));
+		}
+
+		drop(bot);
+		engine.join().unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn tbp_quit_test() {
+
+		// An engine process that ignores `quit` is killed instead of waited for
+		let (mut bot, engine) = connect(|input, output| serve(Box::new(Bot::new(&default_dna())), input, output).unwrap());
+		bot.child = Some(Command::new("sleep").arg("30").spawn().unwrap());
+
+		let start = Instant::now();
+		drop(bot);
+		assert!(start.elapsed() < Duration::from_secs(5));
+		engine.join().unwrap();
+	}
+}
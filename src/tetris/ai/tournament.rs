@@ -0,0 +1,312 @@
+extern crate serde;
+
+use rand::{rngs, Rng, SeedableRng};
+use serde::Serialize;
+use std::f64::consts::PI;
+use super::super::model::board::Board;
+use super::super::model::stats;
+use super::super::model::world::seed_from;
+use super::controller::{Controller, Strategy};
+use super::train;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Winner {
+	First,
+	Second,
+	Draw
+}
+
+/// Headless versus game: both players get the same pieces and place them in
+/// turns, and the lines they clear push garbage into the other board. The
+/// one who tops out loses; after `max_pieces` pieces each it is a draw.
+pub fn versus(first: &mut dyn Controller, second: &mut dyn Controller, seed: [u8; 16], max_pieces: usize) -> Winner {
+
+	let mut boards = [Board::new(seed), Board::new(seed)];
+	let mut rng = rngs::SmallRng::from_seed(seed);
+	let winners = [Winner::Second, Winner::First];
+
+	for _ in 0..max_pieces {
+		for player in 0..2 {
+
+			if !train::is_alive(&boards[player]) {
+				return winners[player];
+			}
+
+			let controller: &mut dyn Controller = if player == 0 { &mut *first } else { &mut *second };
+			let (_, lines) = train::step(controller, &mut boards[player]);
+
+			let garbage = stats::garbage(lines);
+			if garbage > 0 && !boards[1 - player].add_garbage(garbage, rng.gen_range(2, 10)) {
+				return winners[1 - player];
+			}
+		}
+	}
+
+	Winner::Draw
+}
+
+// Ratings
+
+const ELO_K: f64 = 32.0;
+const GLICKO_Q: f64 = 0.005_756_5;
+
+/// Elo rating update of the first player for a result (1 win, 0.5 draw, 0 loss)
+pub fn elo(rating: f64, opponent: f64, result: f64) -> f64 {
+	let expected = 1.0 / (1.0 + 10_f64.powf((opponent - rating) / 400.0));
+	rating + ELO_K * (result - expected)
+}
+
+/// Glicko rating and deviation of the first player after a single game
+pub fn glicko((rating, deviation): (f64, f64), (opponent, opponent_deviation): (f64, f64), result: f64) -> (f64, f64) {
+	let g = 1.0 / (1.0 + 3.0 * GLICKO_Q.powi(2) * opponent_deviation.powi(2) / PI.powi(2)).sqrt();
+	let expected = 1.0 / (1.0 + 10_f64.powf(-g * (rating - opponent) / 400.0));
+	let d2 = 1.0 / (GLICKO_Q.powi(2) * g.powi(2) * expected * (1.0 - expected));
+	let precision = 1.0 / deviation.powi(2) + 1.0 / d2;
+	(rating + GLICKO_Q / precision * g * (result - expected), (1.0 / precision).sqrt().max(30.0))
+}
+
+// Struct: Standing
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Standing {
+	pub name: String,
+	pub wins: usize,
+	pub draws: usize,
+	pub losses: usize,
+	pub elo: f64,
+	pub glicko: f64,
+	pub glicko_deviation: f64,
+	/// Opponents already met (for Swiss pairings)
+	#[serde(skip)]
+	opponents: Vec<usize>
+}
+
+impl Standing {
+
+	fn new(name: &str) -> Self {
+		Self {
+			name: name.into(),
+			wins: 0,
+			draws: 0,
+			losses: 0,
+			elo: 1500.0,
+			glicko: 1500.0,
+			glicko_deviation: 350.0,
+			opponents: vec![]
+		}
+	}
+
+	/// A win is worth a point, a draw half
+	pub fn points(&self) -> f64 {
+		self.wins as f64 + self.draws as f64 / 2.0
+	}
+
+	pub fn played(&self) -> usize {
+		self.wins + self.draws + self.losses
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+	/// Everyone plays everyone
+	RoundRobin,
+	/// Rounds pairing players with similar points who have not met yet
+	Swiss(usize)
+}
+
+// Struct: Tournament
+
+pub struct Tournament {
+	players: Vec<(String, Strategy)>,
+	pub standings: Vec<Standing>,
+	/// Games of every pairing
+	games: usize,
+	max_pieces: usize,
+	/// Games played so far (each one gets the next seed)
+	seed: u64
+}
+
+impl Tournament {
+
+	pub fn new(players: Vec<(String, Strategy)>, games: usize, max_pieces: usize) -> Self {
+		Self {
+			standings: players.iter().map(|(name, _)| Standing::new(name)).collect(),
+			players,
+			games,
+			max_pieces,
+			seed: 0
+		}
+	}
+
+	/// Plays every game of the format. `progress` gets the names and winner of every game.
+	/// Fails with the name of the first player that cannot be built, before any game is played.
+	pub fn run(&mut self, format: Format, progress: &mut dyn FnMut(&str, &str, Winner)) -> Result<(), String> {
+		let mut controllers = self.build()?;
+		let rounds = match format {
+			Format::RoundRobin => vec![self.round_robin()],
+			Format::Swiss(rounds) => vec![vec![]; rounds]
+		};
+		for (round, pairings) in rounds.into_iter().enumerate() {
+			let pairings = if let Format::Swiss(_) = format { self.swiss(round) } else { pairings };
+			for (a, b) in pairings {
+				self.play(&mut controllers, a, b, progress);
+			}
+		}
+		Ok(())
+	}
+
+	/// Controller of every player, built once for the whole tournament (an engine is started once)
+	fn build(&self) -> Result<Vec<Box<dyn Controller>>, String> {
+		self.players.iter()
+			.enumerate()
+			.map(|(index, (name, strategy))| strategy.build(seed_from(index as u64)).ok_or_else(|| name.clone()))
+			.collect()
+	}
+
+	fn round_robin(&self) -> Vec<(usize, usize)> {
+		let n = self.players.len();
+		(0..n).flat_map(|a| (a + 1..n).map(move |b| (a, b))).collect()
+	}
+
+	/// Pairs the players from the most points to the least, each with the next
+	/// one they have not met yet (the first round follows the order given)
+	fn swiss(&self, round: usize) -> Vec<(usize, usize)> {
+
+		let mut order: Vec<usize> = (0..self.players.len()).collect();
+		if round > 0 {
+			order.sort_by(|a, b| self.standings[*b].points().partial_cmp(&self.standings[*a].points()).unwrap());
+		}
+
+		let mut pairings = vec![];
+		while let Some(a) = order.first().cloned() {
+			order.remove(0);
+			if order.is_empty() {
+				break;
+			}
+			let index = order.iter()
+				.position(|b| !self.standings[a].opponents.contains(b))
+				.unwrap_or(0);
+			pairings.push((a, order.remove(index)));
+		}
+		pairings
+	}
+
+	/// Plays the games of a pairing, each player starting first half of the time
+	fn play(&mut self, controllers: &mut [Box<dyn Controller>], a: usize, b: usize, progress: &mut dyn FnMut(&str, &str, Winner)) {
+
+		for game in 0..self.games {
+
+			let seed = seed_from(self.seed);
+			self.seed += 1;
+
+			let (first, second) = if game % 2 == 0 { (a, b) } else { (b, a) };
+			let (low, high) = controllers.split_at_mut(first.max(second));
+			let (x, y) = if first < second { (&mut low[first], &mut high[0]) } else { (&mut high[0], &mut low[second]) };
+			let winner = versus(x.as_mut(), y.as_mut(), seed, self.max_pieces);
+			progress(&self.players[first].0, &self.players[second].0, winner);
+
+			let result = match winner {
+				Winner::First => 1.0,
+				Winner::Second => 0.0,
+				Winner::Draw => 0.5
+			};
+			self.record(first, second, result);
+		}
+	}
+
+	/// Updates both standings with the result of the first player
+	fn record(&mut self, first: usize, second: usize, result: f64) {
+
+		let (x, y) = (self.standings[first].clone(), self.standings[second].clone());
+
+		for (index, me, opponent, result) in [(first, &x, &y, result), (second, &y, &x, 1.0 - result)].iter() {
+			let standing = &mut self.standings[*index];
+			standing.elo = elo(me.elo, opponent.elo, *result);
+			let (rating, deviation) = glicko((me.glicko, me.glicko_deviation), (opponent.glicko, opponent.glicko_deviation), *result);
+			standing.glicko = rating;
+			standing.glicko_deviation = deviation;
+			if *result == 1.0 {
+				standing.wins += 1;
+			}
+			else if *result == 0.0 {
+				standing.losses += 1;
+			}
+			else {
+				standing.draws += 1;
+			}
+		}
+
+		self.standings[first].opponents.push(second);
+		self.standings[second].opponents.push(first);
+	}
+
+	/// Standings from the most points to the least (Elo breaks ties)
+	pub fn table(&self) -> Vec<Standing> {
+		let mut table = self.standings.clone();
+		table.sort_by(|a, b| {
+			(b.points(), b.elo).partial_cmp(&(a.points(), a.elo)).unwrap()
+		});
+		table
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::bot::Bot;
+	use super::super::bot::default_dna;
+	use super::super::random::RandomBot;
+
+	#[test]
+	fn tournament_versus_test() {
+		let seed = [9; 16];
+		let winner = versus(&mut Bot::new(&default_dna()), &mut RandomBot::new(seed), seed, 200);
+		assert_eq!(Winner::First, winner);
+		assert_eq!(Winner::Second, versus(&mut RandomBot::new(seed), &mut Bot::new(&default_dna()), seed, 200));
+	}
+
+	#[test]
+	fn tournament_ratings_test() {
+		assert_eq!(1516.0, elo(1500.0, 1500.0, 1.0));
+		assert_eq!(1484.0, elo(1500.0, 1500.0, 0.0));
+
+		// Example of Glickman's paper, one game at a time
+		let (rating, deviation) = glicko((1500.0, 200.0), (1400.0, 30.0), 1.0);
+		assert!(rating > 1500.0 && deviation < 200.0);
+		let (rating, _) = glicko((1500.0, 200.0), (1700.0, 300.0), 0.5);
+		assert!(rating > 1500.0);
+	}
+
+	#[test]
+	fn tournament_run_test() {
+		let players = vec![
+			("greedy".to_string(), Strategy::Greedy),
+			("random".to_string(), Strategy::Random),
+			("heuristic".to_string(), Strategy::Heuristic(default_dna()))
+		];
+
+		let mut round_robin = Tournament::new(players.clone(), 2, 60);
+		let mut games = 0;
+		round_robin.run(Format::RoundRobin, &mut |_, _, _| games += 1).unwrap();
+		assert_eq!(6, games);
+		assert!(round_robin.table().iter().all(|s| s.played() == 4));
+		assert_eq!("random", round_robin.table()[2].name);
+
+		let mut swiss = Tournament::new(players, 1, 60);
+		swiss.run(Format::Swiss(2), &mut |_, _, _| ()).unwrap();
+		assert_eq!(4, swiss.standings.iter().map(Standing::played).sum::<usize>());
+
+		// The second round avoids the pairing of the first one
+		let first = swiss.standings.iter().position(|s| s.played() == 2).unwrap();
+		assert_ne!(swiss.standings[first].opponents[0], swiss.standings[first].opponents[1]);
+
+		// An engine that does not start fails the tournament instead of forfeiting its games
+		let mut broken = Tournament::new(vec![
+			("greedy".to_string(), Strategy::Greedy),
+			("tbp".to_string(), Strategy::External("./no-such-engine".into()))
+		], 2, 60);
+		assert_eq!(Some("tbp".to_string()), broken.run(Format::RoundRobin, &mut |_, _, _| ()).err());
+		assert!(broken.standings.iter().all(|s| s.played() == 0 && s.elo == 1500.0));
+	}
+}
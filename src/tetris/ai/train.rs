@@ -0,0 +1,253 @@
+
+extern crate rayon;
+
+use rand::{rngs, Rng, SeedableRng};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder, ThreadPoolBuildError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use super::super::model::board::{self, Board, Piece};
+use super::super::model::stats;
+use super::bot::{Bot, Dna};
+use super::features::FEATURES;
+use super::controller::Controller;
+
+/// Whether the next piece can still be played
+pub fn is_alive(board: &Board) -> bool {
+	!board.is_gameover() && !board::collides(board, &board.current)
+}
+
+/// Asks the controller where the current piece goes and locks it there.
+/// Returns where it locked and the lines it cleared.
+pub fn step(controller: &mut dyn Controller, board: &mut Board) -> (Piece, usize) {
+
+	if let Some(target) = controller.ask(board) {
+		if target.shape.letter() == board.current.shape.letter() &&
+			!board::collides(board, &target) && board::down(board, &target).is_err() {
+			board.current = target;
+		}
+	}
+
+	while let Ok(piece) = board::down(board, &board.current) {
+		board.current = piece;
+	}
+
+	let piece = board.current;
+	(piece, board.lock_current())
+}
+
+/// Result of a headless game
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Outcome {
+	pub pieces: usize,
+	pub lines: usize,
+	pub score: usize
+}
+
+/// Plays a game without window until the stack tops out or `max_pieces` pieces are placed.
+/// Illegal answers of the controller are ignored (the piece is dropped where it is).
+pub fn play(controller: &mut dyn Controller, seed: [u8; 16], max_pieces: usize) -> Outcome {
+	play_observed(controller, seed, max_pieces, &mut |_, _, _| ())
+}
+
+/// Same as `play`, showing `observe` every placement: the board before it,
+/// where the piece locked and the lines it cleared
+pub fn play_observed(
+	controller: &mut dyn Controller,
+	seed: [u8; 16],
+	max_pieces: usize,
+	observe: &mut dyn FnMut(&Board, &Piece, usize)
+) -> Outcome {
+
+	let mut board = Board::new(seed);
+	let mut outcome = Outcome::default();
+
+	while outcome.pieces < max_pieces && is_alive(&board) {
+
+		let before = board.clone();
+		let (piece, lines) = step(controller, &mut board);
+		observe(&before, &piece, lines);
+
+		outcome.lines += lines;
+		outcome.score += stats::points(lines);
+		outcome.pieces += 1;
+	}
+
+	outcome
+}
+
+// Struct: Evaluator
+
+/// Plays the games of many genomes concurrently on a thread pool
+pub struct Evaluator {
+	pool: ThreadPool,
+	max_pieces: usize
+}
+
+impl Evaluator {
+
+	/// `threads` set to 0 uses every core
+	pub fn new(threads: usize, max_pieces: usize) -> Result<Self, ThreadPoolBuildError> {
+		Ok(Self {
+			pool: ThreadPoolBuilder::new().num_threads(threads).build()?,
+			max_pieces
+		})
+	}
+
+	/// Outcomes of every genome (in order) on every seed (in order), the same no matter
+	/// how the games were scheduled. `progress` gets the games finished and the total.
+	pub fn evaluate(&self, dnas: &[Dna], seeds: &[[u8; 16]], progress: &(dyn Fn(usize, usize) + Sync)) -> Vec<Vec<Outcome>> {
+
+		if seeds.is_empty() {
+			return dnas.iter().map(|_| vec![]).collect();
+		}
+
+		let total = dnas.len() * seeds.len();
+		let finished = AtomicUsize::new(0);
+
+		let outcomes: Vec<Outcome> = self.pool.install(|| {
+			(0..total).into_par_iter()
+				.map(|game| {
+					let mut bot = Bot::new(&dnas[game / seeds.len()]);
+					let outcome = play(&mut bot, seeds[game % seeds.len()], self.max_pieces);
+					progress(finished.fetch_add(1, Ordering::SeqCst) + 1, total);
+					outcome
+				})
+				.collect()
+		});
+
+		outcomes.chunks(seeds.len())
+			.map(|chunk| chunk.to_vec())
+			.collect()
+	}
+}
+
+// Struct: Training
+
+/// Genetic algorithm looking for the weights that clear the most lines,
+/// every feature of the registry included
+pub struct Training {
+	pub population: usize,
+	pub generations: usize,
+	/// Games every genome plays per generation
+	pub games: usize,
+	pub seed: [u8; 16]
+}
+
+impl Training {
+
+	/// Runs the generations and returns the best genome of the last one.
+	/// `report` gets the generation, its best genome and the lines that genome cleared.
+	pub fn run(&self, evaluator: &Evaluator, progress: &(dyn Fn(usize, usize) + Sync), report: &mut dyn FnMut(usize, Dna, usize)) -> Dna {
+
+		let mut rng = rngs::SmallRng::from_seed(self.seed);
+		let mut population: Vec<Weights> = (0..self.population.max(2)).map(|_| random_weights(&mut rng)).collect();
+		let mut best = to_dna(&population[0]);
+
+		for generation in 0..self.generations {
+
+			// Every genome plays the same games
+			let seeds: Vec<[u8; 16]> = (0..self.games).map(|_| rng.gen()).collect();
+			let dnas: Vec<Dna> = population.iter().map(|weights| to_dna(weights)).collect();
+			let fitness: Vec<usize> = evaluator.evaluate(&dnas, &seeds, progress).iter()
+				.map(|outcomes| outcomes.iter().map(|o| o.lines).sum())
+				.collect();
+
+			// Rank from best to worst
+			let mut ranking: Vec<usize> = (0..population.len()).collect();
+			ranking.sort_by(|a, b| fitness[*b].cmp(&fitness[*a]));
+			best = dnas[ranking[0]].clone();
+			report(generation, best.clone(), fitness[ranking[0]]);
+
+			// The offspring replaces the worst 30%
+			let offspring: Vec<Weights> = (0..population.len() * 3 / 10)
+				.map(|_| {
+					let (a, b) = tournament(&mut rng, &fitness);
+					mutate(&mut rng, crossover((&population[a], fitness[a]), (&population[b], fitness[b])))
+				})
+				.collect();
+			for (index, child) in ranking.iter().rev().zip(offspring) {
+				population[*index] = child;
+			}
+		}
+
+		best
+	}
+}
+
+/// Weights in the order of the registry
+type Weights = Vec<f64>;
+
+fn to_dna(weights: &[f64]) -> Dna {
+	FEATURES.iter().map(|f| f.name.to_string()).zip(weights.iter().cloned()).collect()
+}
+
+fn normalize(mut weights: Weights) -> Weights {
+	let norm = weights.iter().map(|w| w * w).sum::<f64>().sqrt();
+	if norm != 0.0 {
+		weights.iter_mut().for_each(|w| *w /= norm);
+	}
+	weights
+}
+
+fn random_weights(rng: &mut rngs::SmallRng) -> Weights {
+	normalize(FEATURES.iter().map(|_| rng.gen_range(-0.5, 0.5)).collect())
+}
+
+/// Two best genomes of a random 10% of the population
+fn tournament(rng: &mut rngs::SmallRng, fitness: &[usize]) -> (usize, usize) {
+	let mut candidates: Vec<usize> = (0..(fitness.len() / 10).max(2))
+		.map(|_| rng.gen_range(0, fitness.len()))
+		.collect();
+	candidates.sort_by(|a, b| fitness[*b].cmp(&fitness[*a]));
+	(candidates[0], candidates[1])
+}
+
+/// Average of both parents weighted by their fitness
+fn crossover((a, fitness_a): (&[f64], usize), (b, fitness_b): (&[f64], usize)) -> Weights {
+	let (wa, wb) = if fitness_a + fitness_b == 0 { (1.0, 1.0) } else { (fitness_a as f64, fitness_b as f64) };
+	normalize(a.iter().zip(b).map(|(a, b)| a * wa + b * wb).collect())
+}
+
+/// Moves a random weight up to 0.2 in 5% of the genomes
+fn mutate(rng: &mut rngs::SmallRng, mut weights: Weights) -> Weights {
+	if rng.gen_bool(0.05) {
+		let index = rng.gen_range(0, weights.len());
+		weights[index] += rng.gen_range(-0.2, 0.2);
+	}
+	normalize(weights)
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::bot::default_dna;
+
+	#[test]
+	fn train_play_test() {
+		let outcome = play(&mut Bot::new(&default_dna()), [3; 16], 30);
+		assert_eq!(30, outcome.pieces);
+		assert_eq!(outcome, play(&mut Bot::new(&default_dna()), [3; 16], 30));
+	}
+
+	#[test]
+	fn train_evaluate_test() {
+		let dnas = [default_dna(), to_dna(&[0.0, 0.0, 0.0, -1.0])];
+		let seeds = [[1; 16], [2; 16], [3; 16]];
+		let serial = Evaluator::new(1, 20).unwrap().evaluate(&dnas, &seeds, &|_, _| ());
+		let finished = AtomicUsize::new(0);
+		let parallel = Evaluator::new(4, 20).unwrap().evaluate(&dnas, &seeds, &|_, _| {
+			finished.fetch_add(1, Ordering::SeqCst);
+		});
+		assert_eq!(serial, parallel);
+		assert_eq!(2, parallel.len());
+		assert_eq!(6, finished.load(Ordering::SeqCst));
+		assert_eq!(play(&mut Bot::new(&dnas[1]), seeds[2], 20), parallel[1][2]);
+
+		// No games: no outcome for each genome, and a generation still runs
+		let evaluator = Evaluator::new(1, 20).unwrap();
+		assert_eq!(vec![Vec::<Outcome>::new(); 2], evaluator.evaluate(&dnas, &[], &|_, _| ()));
+		let training = Training { population: 4, generations: 1, games: 0, seed: [5; 16] };
+		training.run(&evaluator, &|_, _| (), &mut |_, _, lines| assert_eq!(0, lines));
+	}
+}
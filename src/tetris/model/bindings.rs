@@ -0,0 +1,430 @@
+extern crate serde;
+extern crate toml;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use ggez::event::KeyCode;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error;
+
+/// What a key does on the board of its player
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+	Left,
+	Right,
+	SoftDrop,
+	HardDrop,
+	RotateCw,
+	RotateCcw,
+	Rotate180,
+	Hold,
+	Pause
+}
+
+impl Action {
+
+	pub const ALL: [Action; 9] = [
+		Action::Left, Action::Right, Action::SoftDrop, Action::HardDrop,
+		Action::RotateCw, Action::RotateCcw, Action::Rotate180, Action::Hold, Action::Pause
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Action::Left => "Move left",
+			Action::Right => "Move right",
+			Action::SoftDrop => "Soft drop",
+			Action::HardDrop => "Hard drop",
+			Action::RotateCw => "Rotate clockwise",
+			Action::RotateCcw => "Rotate counterclockwise",
+			Action::Rotate180 => "Rotate 180",
+			Action::Hold => "Hold",
+			Action::Pause => "Pause"
+		}
+	}
+
+	/// Name of the action in the config file
+	pub fn field(self) -> &'static str {
+		match self {
+			Action::Left => "left",
+			Action::Right => "right",
+			Action::SoftDrop => "soft_drop",
+			Action::HardDrop => "hard_drop",
+			Action::RotateCw => "rotate_cw",
+			Action::RotateCcw => "rotate_ccw",
+			Action::Rotate180 => "rotate_180",
+			Action::Hold => "hold",
+			Action::Pause => "pause"
+		}
+	}
+}
+
+/// Keys that can be bound, by the name they have in the config file (Tab and Backspace focus the boards)
+const KEYS: [KeyCode; 73] = [
+	KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::I,
+	KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+	KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+	KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+	KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+	KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4,
+	KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9,
+	KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down,
+	KeyCode::Space, KeyCode::Return, KeyCode::Escape,
+	KeyCode::LShift, KeyCode::RShift, KeyCode::LControl, KeyCode::RControl, KeyCode::LAlt, KeyCode::RAlt,
+	KeyCode::Comma, KeyCode::Period, KeyCode::Slash, KeyCode::Backslash, KeyCode::Semicolon, KeyCode::Apostrophe,
+	KeyCode::LBracket, KeyCode::RBracket, KeyCode::Minus, KeyCode::Equals, KeyCode::Grave,
+	KeyCode::Insert, KeyCode::Delete, KeyCode::NumpadEnter
+];
+
+/// A key, saved by its name (`Left`, `Z`, `LShift`...)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key(pub KeyCode);
+
+impl Key {
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		KEYS.iter().find(|key| format!("{:?}", key) == name).map(|key| Key(*key))
+	}
+
+	/// Whether the key can go in the config file
+	pub fn is_bindable(keycode: KeyCode) -> bool {
+		KEYS.contains(&keycode)
+	}
+}
+
+impl fmt::Display for Key {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:?}", self.0)
+	}
+}
+
+impl Serialize for Key {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&self.to_string())
+	}
+}
+
+impl<'de> Deserialize<'de> for Key {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let name = String::deserialize(deserializer)?;
+		Key::from_name(&name).ok_or_else(|| D::Error::custom(format!("unknown key `{}`", name)))
+	}
+}
+
+fn keys(codes: &[KeyCode]) -> Vec<Key> {
+	codes.iter().map(|code| Key(*code)).collect()
+}
+
+/// How held keys repeat, in frames (60 per second)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Handling {
+	/// Delayed auto shift: frames a move key is held before it repeats
+	pub das: u32,
+	/// Auto repeat rate: frames between two repeated moves (0 shifts to the wall at once)
+	pub arr: u32,
+	/// Times faster than gravity the piece falls while soft dropping (0 drops it at once)
+	pub soft_drop_factor: u32
+}
+
+impl Default for Handling {
+	fn default() -> Self {
+		Self {
+			das: 10,
+			arr: 2,
+			soft_drop_factor: 20
+		}
+	}
+}
+
+/// Keys of one player
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyMap {
+	pub left: Vec<Key>,
+	pub right: Vec<Key>,
+	pub soft_drop: Vec<Key>,
+	pub hard_drop: Vec<Key>,
+	pub rotate_cw: Vec<Key>,
+	pub rotate_ccw: Vec<Key>,
+	pub rotate_180: Vec<Key>,
+	pub hold: Vec<Key>,
+	pub pause: Vec<Key>,
+	pub handling: Handling
+}
+
+impl Default for KeyMap {
+
+	/// Arrows, space to hard drop and shift to hold
+	fn default() -> Self {
+		Self {
+			left: keys(&[KeyCode::Left]),
+			right: keys(&[KeyCode::Right]),
+			soft_drop: keys(&[KeyCode::Down]),
+			hard_drop: keys(&[KeyCode::Space]),
+			rotate_cw: keys(&[KeyCode::Up, KeyCode::X]),
+			rotate_ccw: keys(&[KeyCode::Z]),
+			rotate_180: keys(&[KeyCode::A]),
+			hold: keys(&[KeyCode::LShift, KeyCode::RShift, KeyCode::H]),
+			pause: keys(&[KeyCode::Escape, KeyCode::P]),
+			handling: Handling::default()
+		}
+	}
+}
+
+impl KeyMap {
+
+	/// Keys of a second player on the same keyboard, around I J K L
+	pub fn second() -> Self {
+		Self {
+			left: keys(&[KeyCode::J]),
+			right: keys(&[KeyCode::L]),
+			soft_drop: keys(&[KeyCode::K]),
+			hard_drop: keys(&[KeyCode::M]),
+			rotate_cw: keys(&[KeyCode::I]),
+			rotate_ccw: keys(&[KeyCode::U]),
+			rotate_180: keys(&[KeyCode::O]),
+			hold: keys(&[KeyCode::N]),
+			pause: keys(&[KeyCode::Escape, KeyCode::P]),
+			handling: Handling::default()
+		}
+	}
+
+	/// Keys of a third player on, which has to bind its own (only pausing is shared)
+	pub fn unbound() -> Self {
+		Self {
+			left: vec![],
+			right: vec![],
+			soft_drop: vec![],
+			hard_drop: vec![],
+			rotate_cw: vec![],
+			rotate_ccw: vec![],
+			rotate_180: vec![],
+			hold: vec![],
+			pause: keys(&[KeyCode::Escape, KeyCode::P]),
+			handling: Handling::default()
+		}
+	}
+
+	/// Default keys of the `player`-th map of a file
+	pub fn nth(player: usize) -> Self {
+		match player {
+			0 => Self::default(),
+			1 => Self::second(),
+			_ => Self::unbound()
+		}
+	}
+
+	pub fn keys(&self, action: Action) -> &Vec<Key> {
+		match action {
+			Action::Left => &self.left,
+			Action::Right => &self.right,
+			Action::SoftDrop => &self.soft_drop,
+			Action::HardDrop => &self.hard_drop,
+			Action::RotateCw => &self.rotate_cw,
+			Action::RotateCcw => &self.rotate_ccw,
+			Action::Rotate180 => &self.rotate_180,
+			Action::Hold => &self.hold,
+			Action::Pause => &self.pause
+		}
+	}
+
+	pub fn keys_mut(&mut self, action: Action) -> &mut Vec<Key> {
+		match action {
+			Action::Left => &mut self.left,
+			Action::Right => &mut self.right,
+			Action::SoftDrop => &mut self.soft_drop,
+			Action::HardDrop => &mut self.hard_drop,
+			Action::RotateCw => &mut self.rotate_cw,
+			Action::RotateCcw => &mut self.rotate_ccw,
+			Action::Rotate180 => &mut self.rotate_180,
+			Action::Hold => &mut self.hold,
+			Action::Pause => &mut self.pause
+		}
+	}
+
+	/// Action bound to a key, if any
+	pub fn action(&self, keycode: KeyCode) -> Option<Action> {
+		Action::ALL.iter().cloned().find(|action| self.keys(*action).contains(&Key(keycode)))
+	}
+
+	/// A key bound to two actions
+	pub fn repeated(&self) -> Option<Key> {
+		Action::ALL.iter()
+			.flat_map(|action| self.keys(*action).iter().map(move |key| (*action, *key)))
+			.find(|(action, key)| self.action(key.0) != Some(*action))
+			.map(|(_, key)| key)
+	}
+}
+
+/// Keys of every local player, saved as TOML
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Bindings {
+	/// One map per human board, in the order of the boards (repeated if there are more boards)
+	pub players: Vec<KeyMap>,
+	/// File the bindings are saved to
+	#[serde(skip)]
+	pub path: Option<PathBuf>
+}
+
+impl Default for Bindings {
+	fn default() -> Self {
+		Self {
+			players: vec![KeyMap::default(), KeyMap::second()],
+			path: None
+		}
+	}
+}
+
+impl Bindings {
+
+	/// Bindings of a file, the default ones if it does not exist yet
+	pub fn load(path: PathBuf) -> io::Result<Self> {
+		let mut bindings = if path.exists() {
+			let bindings: Self = toml::from_str(&fs::read_to_string(&path)?)
+				.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+			if bindings.players.is_empty() { Self::default() } else { bindings }
+		}
+		else {
+			Self::default()
+		};
+		bindings.path = Some(path);
+		Ok(bindings)
+	}
+
+	/// Binds a key to an action of a player only, taking it from the other actions of every player
+	/// (pausing can share its keys, as it pauses the whole game)
+	pub fn bind(&mut self, player: usize, action: Action, keycode: KeyCode) {
+		let player = player % self.players.len();
+		for (index, map) in self.players.iter_mut().enumerate() {
+			for other in Action::ALL.iter() {
+				if index == player || action != Action::Pause || *other != Action::Pause {
+					map.keys_mut(*other).retain(|key| key.0 != keycode);
+				}
+			}
+		}
+		self.players[player].keys_mut(action).push(Key(keycode));
+	}
+
+	/// A key two players use, other than to pause
+	pub fn shared(&self) -> Option<(Key, usize, usize)> {
+		for (first, map) in self.players.iter().enumerate() {
+			for (second, other) in self.players.iter().enumerate().skip(first + 1) {
+				for action in Action::ALL.iter() {
+					for key in map.keys(*action) {
+						match other.action(key.0) {
+							Some(Action::Pause) if *action == Action::Pause => {},
+							Some(_) => return Some((*key, first, second)),
+							None => {}
+						}
+					}
+				}
+			}
+		}
+		None
+	}
+
+	/// Writes the bindings back to the file they came from
+	pub fn save(&self) -> io::Result<()> {
+		let path = self.path.as_ref().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no file for the key bindings"))?;
+		let content = toml::to_string(self)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+		fs::write(path, content)
+	}
+
+	/// Keys of the `player`-th human board
+	pub fn map(&self, player: usize) -> &KeyMap {
+		&self.players[player % self.players.len()]
+	}
+
+	pub fn map_mut(&mut self, player: usize) -> &mut KeyMap {
+		let len = self.players.len();
+		&mut self.players[player % len]
+	}
+}
+
+impl<'de> Deserialize<'de> for Bindings {
+
+	/// Actions left out of a map keep its default keys, but for the keys the file binds to other actions.
+	/// No key may be bound to two actions, or shared by two players.
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+
+		#[derive(Deserialize)]
+		struct File {
+			#[serde(default)]
+			players: Vec<toml::value::Table>
+		}
+
+		let file = File::deserialize(deserializer)?;
+
+		let mut players = vec![];
+		for (index, table) in file.players.into_iter().enumerate() {
+			let (bound, defaults): (Vec<Action>, Vec<Action>) = Action::ALL.iter().partition(|action| table.contains_key(action.field()));
+			let mut merged = match toml::Value::try_from(KeyMap::nth(index)) {
+				Ok(toml::Value::Table(merged)) => merged,
+				_ => return Err(D::Error::custom("could not write the default keys"))
+			};
+			merged.extend(table);
+			let mut map = toml::Value::Table(merged).try_into::<KeyMap>().map_err(D::Error::custom)?;
+
+			let taken: Vec<Key> = bound.iter().flat_map(|action| map.keys(*action).clone()).collect();
+			for action in defaults {
+				map.keys_mut(action).retain(|key| !taken.contains(key));
+			}
+			if let Some(key) = map.repeated() {
+				return Err(D::Error::custom(format!("key `{}` is bound to two actions of player {}", key, index + 1)));
+			}
+			players.push(map);
+		}
+
+		let bindings = Self { players, path: None };
+		match bindings.shared() {
+			Some((key, first, second)) => Err(D::Error::custom(format!("key `{}` is used by players {} and {}", key, first + 1, second + 1))),
+			None => Ok(bindings)
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	#[test]
+	fn bindings_toml_test() {
+		let mut bindings = Bindings::default();
+		bindings.bind(0, Action::HardDrop, KeyCode::Up);
+		assert_eq!(Some(Action::HardDrop), bindings.map(0).action(KeyCode::Up));
+		assert_eq!(vec![Key(KeyCode::X)], bindings.map(0).rotate_cw);
+		assert_eq!(Some(Action::Left), bindings.map(3).action(KeyCode::J));
+
+		// A key taken by another player leaves the first, pausing keys are shared
+		bindings.bind(1, Action::Hold, KeyCode::Space);
+		assert_eq!((None, Some(Action::Hold)), (bindings.map(0).action(KeyCode::Space), bindings.map(1).action(KeyCode::Space)));
+		bindings.bind(1, Action::Pause, KeyCode::Q);
+		assert_eq!(Some(Action::Pause), bindings.map(0).action(KeyCode::P));
+		assert_eq!(None, bindings.shared());
+
+		// Through the file format, and actions left out keep the default keys
+		let content = toml::to_string(&bindings).unwrap();
+		assert!(content.contains("hard_drop = [\"Up\"]"));
+		assert_eq!(bindings, toml::from_str(&content).unwrap());
+
+		let partial: Bindings = toml::from_str("[[players]]\nhold = [\"C\"]\n[players.handling]\narr = 0\n").unwrap();
+		assert_eq!(Some(Action::Hold), partial.map(0).action(KeyCode::C));
+		assert_eq!((10, 0), (partial.map(0).handling.das, partial.map(0).handling.arr));
+		assert_eq!(Some(Action::Left), partial.map(0).action(KeyCode::Left));
+		assert!(toml::from_str::<Bindings>("[[players]]\nhold = [\"Nope\"]\n").is_err());
+
+		// The second player keeps its own keys, and cannot take those of the first
+		let two: Bindings = toml::from_str("[[players]]\n[[players]]\nhold = [\"B\"]\n").unwrap();
+		assert_eq!((Some(Action::Left), Some(Action::Hold)), (two.map(1).action(KeyCode::J), two.map(1).action(KeyCode::B)));
+		assert!(toml::from_str::<Bindings>("[[players]]\n[[players]]\nhold = [\"Space\"]\n").is_err());
+		assert!(!Key::is_bindable(KeyCode::Tab) && Key::from_name("Back").is_none());
+
+		// A key the file binds leaves the default action that had it, but the file cannot bind it twice
+		let moved: Bindings = toml::from_str("[[players]]\nleft = [\"A\"]\n").unwrap();
+		assert_eq!((Some(Action::Left), true), (moved.map(0).action(KeyCode::A), moved.map(0).rotate_180.is_empty()));
+		assert!(toml::from_str::<Bindings>("[[players]]\nleft = [\"A\"]\nhold = [\"A\"]\n").is_err());
+	}
+}
@@ -1,7 +1,10 @@
 
 use na::{Vec2, MatrixMN, U22, U12, zero};
+use std::collections::VecDeque;
 use rand::{rngs, Rng, SeedableRng};
-use super::shape::{self, Shape};
+use std::fmt;
+use std::str::FromStr;
+use super::shape::Shape;
 
 // Struct: Piece
 
@@ -43,14 +46,104 @@ impl Piece {
 			shape
 		}
 	}
+
+	/// Grid cells (row, column) covered by the piece
+	pub fn cells(&self) -> Vec<(usize, usize)> {
+
+		let value = self.shape.value();
+		let mut cells = vec![];
+
+		for i in 0..value.nrows() {
+			for j in 0..value.ncols() {
+				if value[(i, j)] != 0 {
+					cells.push((self.position.y + i, self.position.x + j));
+				}
+			}
+		}
+
+		cells
+	}
+
+	/// Finds the rotation and position of `shape` that covers exactly the cells given
+	pub fn from_cells(shape: Shape, cells: &[(usize, usize)]) -> Option<Self> {
+
+		let top = cells.iter().map(|(i, _)| *i).min()?;
+		let left = cells.iter().map(|(_, j)| *j).min()?;
+
+		let mut expected = cells.to_vec();
+		expected.sort();
+
+		(0..4)
+			.map(|rotation| shape.with_rotation(rotation))
+			.filter(|shape| top >= shape.y() && left >= shape.x())
+			.map(|shape| Self {
+				position: Vec2::new(left - shape.x(), top - shape.y()),
+				shape
+			})
+			.find(|piece| {
+				let mut covered = piece.cells();
+				covered.sort();
+				covered == expected
+			})
+	}
+
+	/// Location of the piece as external tools see it: the SRS rotation state
+	/// and centre, in playfield coordinates (`x` from the left wall, `y` up from the floor)
+	pub fn srs_location(&self) -> (Shape, i64, i64) {
+
+		let mut blocks: Vec<(i64, i64)> = self.cells()
+			.iter()
+			.map(|(i, j)| (*j as i64 - 2, 19 - *i as i64))
+			.collect();
+		blocks.sort();
+
+		for rotation in 0..4 {
+			let shape = self.shape.with_rotation(rotation);
+			for (x, y) in blocks.iter() {
+				let mut covered: Vec<(i64, i64)> = shape.srs_offsets()
+					.iter()
+					.map(|(dx, dy)| (x + dx, y + dy))
+					.collect();
+				covered.sort();
+				if covered == blocks {
+					return (shape, *x, *y);
+				}
+			}
+		}
+
+		unreachable!()
+	}
+
+	/// Inverse of `srs_location`. Fails if the piece does not fit inside the playfield.
+	pub fn from_srs_location(shape: Shape, x: i64, y: i64) -> Option<Self> {
+
+		let cells = shape.srs_offsets()
+			.iter()
+			.map(|(dx, dy)| (x + dx, y + dy))
+			.map(|(x, y)| {
+				if (0..8).contains(&x) && (0..18).contains(&y) {
+					Some(((19 - y) as usize, (x + 2) as usize))
+				}
+				else {
+					None
+				}
+			})
+			.collect::<Option<Vec<_>>>()?;
+
+		Self::from_cells(shape, &cells)
+	}
 }
 
 // Struct: PieceCollector
 
+/// Shapes known ahead of the current one
+pub const PREVIEW: usize = 5;
+
 #[derive(Clone)]
 pub struct PieceCollector {
 	current_shape: u8,
-	next_shape: u8,
+	/// Next shapes, drawn ahead in the order they come
+	queue: VecDeque<u8>,
 	rng: rngs::SmallRng,
 }
 
@@ -60,11 +153,11 @@ impl PieceCollector {
 
 		let mut rng = rngs::SmallRng::from_seed(seed);
 		let current_shape = rng.gen_range(1_u8, 8_u8);
-		let next_shape = rng.gen_range(1_u8, 8_u8);
+		let queue = (0..PREVIEW).map(|_| rng.gen_range(1_u8, 8_u8)).collect();
 
 		Self {
 			current_shape,
-			next_shape,
+			queue,
 			rng,
 		}
 	}
@@ -73,13 +166,26 @@ impl PieceCollector {
 		Shape::from_index(self.current_shape).unwrap()
 	}
 
-	fn get_next(&self) -> Shape {
-		Shape::from_index(self.next_shape).unwrap()
+	pub fn get_next(&self) -> Shape {
+		Shape::from_index(self.queue[0]).unwrap()
+	}
+
+	/// The `PREVIEW` next shapes
+	pub fn preview(&self) -> Vec<Shape> {
+		self.queue.iter().map(|index| Shape::from_index(*index).unwrap()).collect()
+	}
+
+	pub fn set_current(&mut self, shape: Shape) {
+		self.current_shape = shape.index();
+	}
+
+	pub fn set_next(&mut self, shape: Shape) {
+		self.queue[0] = shape.index();
 	}
 
 	pub fn next(&mut self) {
-		self.current_shape = self.next_shape;
-		self.next_shape = self.rng.gen_range(1_u8, 8_u8);
+		self.current_shape = self.queue.pop_front().unwrap();
+		self.queue.push_back(self.rng.gen_range(1_u8, 8_u8));
 	}
 }
 
@@ -89,7 +195,10 @@ impl PieceCollector {
 pub struct Board {
 	pub collector: PieceCollector,
 	pub grid: MatrixMN<u8, U22, U12>,
-	pub current: Piece
+	pub current: Piece,
+	/// Shape put aside, and whether the current piece already came from the hold
+	pub hold: Option<Shape>,
+	held: bool
 }
 
 impl Board {
@@ -118,6 +227,8 @@ impl Board {
 			grid,
 			collector,
 			current: current_piece,
+			hold: None,
+			held: false,
 		}
 	}
 
@@ -149,9 +260,6 @@ impl Board {
 
 			if self.grid.row(i).iter().filter(|x| **x == 0).count() == 2 {
 
-				// Log success
-				leg::success("Line completed", "\u{1f37b}".into(), None);
-
 				// Count
 				count_lines += 1;
 
@@ -168,46 +276,74 @@ impl Board {
 		count_lines
 	}
 
-	/// Moves the current piece to the x specified
-	pub fn move_current_to(&mut self, x: usize) {
+	/// Locks the current piece, removes the full lines and spawns the next piece.
+	/// Returns the number of lines removed.
+	pub fn lock_current(&mut self) -> usize {
 
-		let mut tries = 0;
+		self.place_current_piece();
+		let lines = self.remove_full_lines();
 
-		while self.current.position.x < x && tries < 5 {
-			if let Ok(piece) = right(&self, &self.current) {
-				self.current = piece
-			}
-			tries += 1;
+		self.collector.next();
+		self.current = self.spawn(self.collector.get_current());
+		self.held = false;
+
+		lines
+	}
+
+	/// Puts the current piece aside, taking the one held before (or the next one).
+	/// Returns `false` if the current piece already came from the hold.
+	pub fn hold(&mut self) -> bool {
+
+		if self.held {
+			return false;
 		}
 
-		while self.current.position.x > x && tries < 5 {
-			if let Ok(piece) = left(&self, &self.current) {
-				self.current = piece
-			}
-			tries += 1;
+		let shape = Shape::from_index(self.current.shape.index()).unwrap();
+		match self.hold.replace(shape) {
+			Some(held) => self.collector.set_current(held),
+			None => self.collector.next()
 		}
+		self.current = self.spawn(self.collector.get_current());
+		self.held = true;
+
+		true
+	}
+
+	/// Whether the current piece can still be put aside
+	pub fn can_hold(&self) -> bool {
+		!self.held
 	}
 
-	/// Rotates the current piece the degrees specified (clockwise)
-	pub fn rotate_current(&mut self, degrees: shape::Rotation) {
-		match degrees {
-			shape::Rotation::Rotate0 => (),
-			shape::Rotation::Rotate90 => {
-				self.current.shape = self.current.shape
-					.rotate_clockwise()
-			},
-			shape::Rotation::Rotate180 => {
-				self.current.shape = self.current.shape
-					.rotate_clockwise()
-					.rotate_clockwise()
-			},
-			shape::Rotation::Rotate270 => {
-				self.current.shape = self.current.shape
-					.rotate_clockwise()
-					.rotate_clockwise()
-					.rotate_clockwise()
-			},
+	/// Pushes the stack up with garbage lines, all filled but the column `hole`.
+	/// Returns `false` if blocks were pushed out of the playfield.
+	pub fn add_garbage(&mut self, lines: usize, hole: usize) -> bool {
+
+		let lines = lines.min(18);
+		let fits = (2..2 + lines).all(|i| (2..10).all(|j| self.grid[(i, j)] == 0));
+
+		for i in 2..20 - lines {
+			for j in 2..10 {
+				self.grid[(i, j)] = self.grid[(i + lines, j)];
+			}
 		}
+		for i in 20 - lines..20 {
+			for j in 2..10 {
+				self.grid[(i, j)] = if j == hole { 0 } else { 8 };
+			}
+		}
+
+		fits
+	}
+
+	/// Piece of the shape given where new pieces appear
+	pub fn spawn(&self, shape: Shape) -> Piece {
+		Piece::new(self.grid.ncols() / 2 - shape.w() / 2, shape)
+	}
+
+	/// Replaces the current piece, keeping the collector in sync
+	pub fn set_current(&mut self, piece: Piece) {
+		self.collector.set_current(piece.shape);
+		self.current = piece;
 	}
 
 	/// Checks if you have reached the top
@@ -216,6 +352,123 @@ impl Board {
 	}
 }
 
+// Text notation
+//
+// The board is written as the 20 x 10 box drawn on screen (walls included):
+// `#` for walls and garbage, `.` for empty cells, `IJLOSTZ` for placed blocks
+// and the same letters in lowercase for the cells of the current piece.
+
+fn cell_to_char(cell: u8) -> char {
+	match Shape::from_index(cell) {
+		Some(shape) => shape.letter(),
+		None if cell == 0 => '.',
+		None => '#'
+	}
+}
+
+impl fmt::Display for Board {
+
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+		let current = self.current.cells();
+		let letter = self.current.shape.letter().to_ascii_lowercase();
+
+		for i in 1..21 {
+			for j in 1..11 {
+				if current.contains(&(i, j)) {
+					write!(f, "{}", letter)?;
+				}
+				else {
+					write!(f, "{}", cell_to_char(self.grid[(i, j)]))?;
+				}
+			}
+			writeln!(f)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseBoardError {
+	InvalidSize,
+	InvalidCell(char),
+	MissingWall,
+	InvalidPiece
+}
+
+impl FromStr for Board {
+
+	type Err = ParseBoardError;
+
+	/// Parses the text notation. Blank lines and indentation are ignored.
+	/// If no current piece is drawn, the collector's one is spawned as usual.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+		let rows: Vec<Vec<char>> = s.lines()
+			.map(|line| line.trim())
+			.filter(|line| !line.is_empty())
+			.map(|line| line.chars().collect())
+			.collect();
+
+		if rows.len() != 20 || rows.iter().any(|row| row.len() != 10) {
+			return Err(ParseBoardError::InvalidSize);
+		}
+
+		let mut board = Board::new([0; 16]);
+		let mut piece: Option<Shape> = None;
+		let mut cells = vec![];
+
+		for (index, row) in rows.iter().enumerate() {
+			for (jndex, c) in row.iter().enumerate() {
+
+				let (i, j) = (index + 1, jndex + 1);
+				let is_wall = i == 1 || i == 20 || j == 1 || j == 10;
+
+				if is_wall {
+					if *c != '#' {
+						return Err(ParseBoardError::MissingWall);
+					}
+					continue;
+				}
+
+				board.grid[(i, j)] = match *c {
+					'.' => 0,
+					'#' => 8,
+					c if c.is_ascii_lowercase() => {
+						let shape = Shape::from_letter(c).ok_or(ParseBoardError::InvalidCell(c))?;
+						if piece.is_some() && piece != Some(shape) {
+							return Err(ParseBoardError::InvalidPiece);
+						}
+						piece = Some(shape);
+						cells.push((i, j));
+						0
+					},
+					c => Shape::from_letter(c).ok_or(ParseBoardError::InvalidCell(c))?.index()
+				};
+			}
+		}
+
+		if let Some(shape) = piece {
+			board.set_current(Piece::from_cells(shape, &cells).ok_or(ParseBoardError::InvalidPiece)?);
+		}
+
+		Ok(board)
+	}
+}
+
+/// Actions a player can take on the falling piece
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Input {
+	Left,
+	Right,
+	/// Clockwise
+	Rotate,
+	RotateCcw,
+	Rotate180,
+	SoftDrop
+}
+
 pub enum BoardError {
 	TouchingGround,
 	UnableToMove,
@@ -226,7 +479,7 @@ pub enum BoardError {
 pub fn down(board: &Board, piece: &Piece) -> Result<Piece, BoardError> {
 
 	if can_down(board, piece) {
-		Ok(piece.clone().y(piece.position.y + 1))
+		Ok((*piece).y(piece.position.y + 1))
 	}
 	else {
 		Err(BoardError::TouchingGround)
@@ -236,7 +489,7 @@ pub fn down(board: &Board, piece: &Piece) -> Result<Piece, BoardError> {
 /// Moves the piece one position to the right if possible
 pub fn right(board: &Board, piece: &Piece) -> Result<Piece, BoardError> {
 	if can_right(board, piece) {
-		Ok(piece.clone().x(piece.position.x + 1))
+		Ok((*piece).x(piece.position.x + 1))
 	}
 	else {
 		Err(BoardError::UnableToMove)
@@ -246,16 +499,33 @@ pub fn right(board: &Board, piece: &Piece) -> Result<Piece, BoardError> {
 /// Moves the piece one position to the left if possible
 pub fn left(board: &Board, piece: &Piece) -> Result<Piece, BoardError> {
 	if can_left(board, piece) {
-		Ok(piece.clone().x(piece.position.x - 1))
+		Ok((*piece).x(piece.position.x - 1))
 	}
 	else {
 		Err(BoardError::UnableToMove)
 	}
 }
 
+/// Applies an input to the piece if possible
+pub fn apply(board: &Board, piece: &Piece, input: Input) -> Result<Piece, BoardError> {
+	match input {
+		Input::Left => left(board, piece),
+		Input::Right => right(board, piece),
+		Input::Rotate => rotate(board, piece),
+		Input::RotateCcw => can_rotate(board, piece, piece.shape.rotate_counterclockwise()),
+		Input::Rotate180 => can_rotate(board, piece, piece.shape.rotate_clockwise().rotate_clockwise()),
+		Input::SoftDrop => down(board, piece)
+	}
+}
+
+/// Checks if the piece overlaps a block or a wall of the board
+pub fn collides(board: &Board, piece: &Piece) -> bool {
+	overlapping(board, piece, 0, 0)
+}
+
 /// Rotates the piece if possible
 pub fn rotate(board: &Board, piece: &Piece) -> Result<Piece, BoardError> {
-	can_rotate(board, piece)
+	can_rotate(board, piece, piece.shape.rotate_clockwise())
 }
 
 // Conditions
@@ -272,9 +542,9 @@ fn can_right(board: &Board, piece: &Piece) -> bool {
 	!overlapping(board, piece, 1, 0)
 }
 
-fn can_rotate(board: &Board, piece: &Piece) -> Result<Piece, BoardError> {
+fn can_rotate(board: &Board, piece: &Piece, shape: Shape) -> Result<Piece, BoardError> {
 
-	let mut new_piece = piece.clone().shape(piece.shape.rotate_clockwise());
+	let mut new_piece = (*piece).shape(shape);
 
 	let mut i = 0;
 	while i < 4 && overlapping(board, &new_piece, 0, 0) {
@@ -310,8 +580,8 @@ fn overlapping(board: &Board, piece: &Piece, offset_x: i64, offset_y: i64) -> bo
 
 	if  (global_offset_y as i64 + y as i64 + offset_y) < 0 ||
 		(global_offset_x as i64 + x as i64 + offset_x) < 0 ||
-		(global_offset_y as i64 + y as i64 + offset_y) as usize >= board.grid.nrows() ||
-		(global_offset_x as i64 + x as i64 + offset_x) as usize >= board.grid.ncols() {
+		(global_offset_y as i64 + y as i64 + offset_y) as usize + h > board.grid.nrows() ||
+		(global_offset_x as i64 + x as i64 + offset_x) as usize + w > board.grid.ncols() {
 		return true;
 	}
 
@@ -325,3 +595,129 @@ fn overlapping(board: &Board, piece: &Piece, offset_x: i64, offset_y: i64) -> bo
 		.zip(piece.shape.value().slice((y, x), (h, w)).iter())
 		.any(|(x1, x2)| *x1 != 0 && *x2 != 0)
 }
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	const BOARD: &str = "
+		##########
+		#........#
+		#...t....#
+		#..ttt...#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#........#
+		#O.......#
+		#OO...S..#
+		#IIIIJJZZ#
+		##########
+	";
+
+	fn normalize(s: &str) -> String {
+		s.lines()
+			.map(|line| line.trim())
+			.filter(|line| !line.is_empty())
+			.map(|line| format!("{}\n", line))
+			.collect()
+	}
+
+	#[test]
+	fn board_round_trip_test() {
+		let board: Board = BOARD.parse().unwrap();
+		assert_eq!(normalize(BOARD), board.to_string());
+	}
+
+	#[test]
+	fn board_parse_cells_test() {
+		let board: Board = BOARD.parse().unwrap();
+		assert_eq!(4, board.grid[(17, 2)]);
+		assert_eq!(5, board.grid[(18, 7)]);
+		assert_eq!(1, board.grid[(19, 2)]);
+		assert_eq!(0, board.grid[(4, 5)]);
+		assert_eq!(8, board.grid[(20, 5)]);
+	}
+
+	#[test]
+	fn board_parse_piece_test() {
+		let board: Board = BOARD.parse().unwrap();
+		assert!(board.current.shape == Shape::T(0));
+		assert_eq!(Vec2::new(4, 3), board.current.position);
+		assert!(board.collector.get_current() == Shape::T(0));
+	}
+
+	#[test]
+	fn board_parse_rotated_piece_test() {
+		let board: Board = BOARD.replace("#...t....#", "#..i.....#")
+			.replace("#..ttt...#", "#..i.....#")
+			.replacen("#........#", "#..i.....#", 1)
+			.replacen("#........#", "#..i.....#", 1)
+			.parse()
+			.unwrap();
+		assert!(board.current.shape == Shape::I(1) || board.current.shape == Shape::I(3));
+		assert_eq!(vec![(2, 4), (3, 4), (4, 4), (5, 4)], board.current.cells());
+	}
+
+	#[test]
+	fn board_parse_errors_test() {
+		assert_eq!(Some(ParseBoardError::InvalidSize), "#..#".parse::<Board>().err());
+		assert_eq!(Some(ParseBoardError::InvalidCell('x')), BOARD.replace("#O......", "#x......").parse::<Board>().err());
+		assert_eq!(Some(ParseBoardError::MissingWall), BOARD.replace("#O.......#", "#O........").parse::<Board>().err());
+		assert_eq!(Some(ParseBoardError::InvalidPiece), BOARD.replace("#...t....#", "#...t..t.#").parse::<Board>().err());
+		assert_eq!(Some(ParseBoardError::InvalidPiece), BOARD.replace("#...t....#", "#...s....#").parse::<Board>().err());
+	}
+
+	#[test]
+	fn board_remove_full_lines_test() {
+		let mut board: Board = BOARD.parse().unwrap();
+		assert_eq!(1, board.remove_full_lines());
+		assert_eq!(4, board.grid[(19, 2)]);
+		assert_eq!(5, board.grid[(19, 7)]);
+		assert_eq!(4, board.grid[(18, 2)]);
+		assert_eq!(0, board.grid[(18, 3)]);
+	}
+
+	#[test]
+	fn board_add_garbage_test() {
+		let mut board: Board = BOARD.parse().unwrap();
+		assert!(board.add_garbage(2, 4));
+		assert_eq!(8, board.grid[(19, 2)]);
+		assert_eq!(0, board.grid[(19, 4)]);
+		assert_eq!(1, board.grid[(17, 2)]);
+		assert_eq!(4, board.grid[(15, 2)]);
+
+		// The O reaches the top
+		assert!(!board.add_garbage(15, 2));
+	}
+
+	#[test]
+	fn board_hold_test() {
+		let mut board = Board::new([8; 16]);
+		let first = board.current.shape.index();
+		let preview = board.collector.preview();
+		assert_eq!(PREVIEW, preview.len());
+
+		// The first hold takes the next piece, once per piece
+		assert!(board.hold());
+		assert_eq!(Some(first), board.hold.map(Shape::index));
+		assert_eq!(preview[0].index(), board.current.shape.index());
+		assert!(preview[1..] == board.collector.preview()[..PREVIEW - 1]);
+		assert!(!board.hold());
+
+		// After a lock, holding swaps
+		board.lock_current();
+		let current = board.current.shape.index();
+		assert!(board.hold());
+		assert_eq!((first, Some(current)), (board.current.shape.index(), board.hold.map(Shape::index)));
+	}
+}
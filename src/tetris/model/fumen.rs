@@ -0,0 +1,672 @@
+
+use super::board::{Board, Piece};
+use super::shape::Shape;
+
+// Fumen v115
+//
+// The field is 10 x 23 plus a garbage row, stored top to bottom. Our 8 x 18
+// playfield is aligned to its bottom left corner, so positions that use the
+// two rightmost columns or the five top rows cannot be imported.
+
+const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE_LEN: u32 = 96;
+const WIDTH: usize = 10;
+const TOP: usize = 23;
+const BLOCKS: usize = (TOP + 1) * WIDTH;
+
+type Field = [u8; BLOCKS];
+
+// Struct: Page
+
+#[derive(Clone)]
+pub struct Page {
+	pub board: Board,
+	/// Whether `board.current` is part of the diagram
+	pub piece: bool,
+	pub comment: Option<String>
+}
+
+impl Page {
+
+	pub fn new(board: Board) -> Self {
+		Self {
+			board,
+			piece: true,
+			comment: None
+		}
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FumenError {
+	InvalidPrefix,
+	InvalidData,
+	OutOfBounds,
+	InvalidPiece
+}
+
+/// Encodes the pages as a v115 fumen string
+pub fn encode(pages: &[Page]) -> String {
+
+	let mut values = vec![];
+	let mut prev: Field = [0; BLOCKS];
+	let mut prev_comment = String::new();
+	let mut repeat_index: Option<usize> = None;
+
+	for (index, page) in pages.iter().enumerate() {
+
+		// Field
+		let field = field_from_board(&page.board);
+		let runs = diff_runs(&prev, &field);
+
+		if runs.len() == 1 && runs[0].0 == 8 {
+			match repeat_index {
+				Some(i) if values[i] < 63 => values[i] += 1,
+				_ => {
+					push(&mut values, 8 * BLOCKS as u32 + BLOCKS as u32 - 1, 2);
+					values.push(0);
+					repeat_index = Some(values.len() - 1);
+				}
+			}
+		}
+		else {
+			for (diff, count) in runs {
+				push(&mut values, diff * BLOCKS as u32 + count - 1, 2);
+			}
+			repeat_index = None;
+		}
+
+		// Action
+		let comment = page.comment.clone().unwrap_or_default();
+		let has_comment = comment != prev_comment;
+		let piece = if page.piece { Some(page.board.current) } else { None };
+		let (kind, rotation, position) = piece.map(encode_piece).unwrap_or((0, 0, 0));
+
+		// Flags: not lock, comment, colour, mirror, rise
+		let flags = [false, has_comment, index == 0, false, false];
+		let mut action = flags.iter().fold(0_u32, |acc, flag| acc * 2 + *flag as u32);
+		action = action * BLOCKS as u32 + position;
+		action = action * 4 + rotation;
+		action = action * 8 + kind;
+		push(&mut values, action, 3);
+
+		// Comment
+		if has_comment {
+			let escaped = escape(&comment);
+			let chars: Vec<u32> = escaped.chars().take(4095).map(|c| c as u32 - 32).collect();
+			push(&mut values, chars.len() as u32, 2);
+			for chunk in chars.chunks(4) {
+				let value = chunk.iter().rev().fold(0, |acc, c| acc * COMMENT_TABLE_LEN + c);
+				push(&mut values, value, 5);
+			}
+			prev_comment = comment;
+		}
+
+		// Lock
+		prev = field;
+		if let Some(piece) = piece {
+			for (x, y) in piece_blocks(&piece) {
+				prev[index_of(x, y)] = to_fumen(piece.shape.index());
+			}
+		}
+		clear_lines(&mut prev);
+	}
+
+	let data: String = values.iter().map(|v| TABLE[*v as usize] as char).collect();
+
+	wrap(&data)
+}
+
+/// Breaks the data with a `?` every 47 characters, the way fumen does (the first chunk counts the prefix)
+fn wrap(data: &str) -> String {
+
+	let mut output = String::from("v115@");
+	if data.len() <= 42 {
+		output.push_str(data);
+	}
+	else {
+		output.push_str(&data[..42]);
+		for chunk in data.as_bytes()[42..].chunks(47) {
+			output.push('?');
+			output.push_str(std::str::from_utf8(chunk).unwrap());
+		}
+	}
+
+	output
+}
+
+/// Decodes a v115 fumen string (or an URL containing it) into its pages
+pub fn decode(data: &str) -> Result<Vec<Page>, FumenError> {
+
+	let start = data.find("115@").ok_or(FumenError::InvalidPrefix)?;
+	if !data[..start].ends_with(|c| "vmd".contains(c)) {
+		return Err(FumenError::InvalidPrefix);
+	}
+
+	let data = data[start + 4..]
+		.chars()
+		.filter(|c| *c != '?' && !c.is_whitespace())
+		.map(|c| TABLE.iter().position(|t| *t as char == c).map(|v| v as u32))
+		.collect::<Option<Vec<u32>>>()
+		.ok_or(FumenError::InvalidData)?;
+
+	let mut values = Values { data, index: 0 };
+
+	let mut pages = vec![];
+	let mut prev: Field = [0; BLOCKS];
+	let mut comment = String::new();
+	let mut repeat = 0;
+
+	while !values.is_empty() {
+
+		// Field
+		let mut field = prev;
+		if repeat > 0 {
+			repeat -= 1;
+		}
+		else {
+			let mut index = 0;
+			while index < BLOCKS {
+				let run = values.poll(2)?;
+				let diff = (run / BLOCKS as u32) as i32 - 8;
+				let count = (run % BLOCKS as u32) as usize + 1;
+				if index + count > BLOCKS {
+					return Err(FumenError::InvalidData);
+				}
+				for cell in field[index..index + count].iter_mut() {
+					let value = *cell as i32 + diff;
+					if !(0..=8).contains(&value) {
+						return Err(FumenError::InvalidData);
+					}
+					*cell = value as u8;
+				}
+				if diff == 0 && count == BLOCKS {
+					repeat = values.poll(1)?;
+				}
+				index += count;
+			}
+		}
+
+		// Action
+		let mut action = values.poll(3)?;
+		let kind = action % 8; action /= 8;
+		let rotation = action % 4; action /= 4;
+		let position = action % BLOCKS as u32; action /= BLOCKS as u32;
+		let rise = action % 2 == 1; action /= 2;
+		let mirror = action % 2 == 1; action /= 2;
+		action /= 2; // colour
+		let has_comment = action % 2 == 1; action /= 2;
+		let lock = action % 2 == 0;
+
+		// Comment
+		if has_comment {
+			let len = values.poll(2)? as usize;
+			let mut escaped = String::new();
+			for _ in 0..len.div_ceil(4) {
+				let mut value = values.poll(5)?;
+				for _ in 0..4 {
+					escaped.push(std::char::from_u32(value % COMMENT_TABLE_LEN + 32).unwrap_or(' '));
+					value /= COMMENT_TABLE_LEN;
+				}
+			}
+			escaped.truncate(len);
+			comment = unescape(&escaped);
+		}
+
+		// Page
+		let piece = if kind == 0 { None } else { Some(decode_piece(kind, rotation, position)?) };
+		let mut board = board_from_field(&field)?;
+		if let Some(piece) = piece {
+			board.set_current(piece);
+		}
+
+		pages.push(Page {
+			board,
+			piece: piece.is_some(),
+			comment: if comment.is_empty() { None } else { Some(comment.clone()) }
+		});
+
+		// Lock
+		prev = field;
+		if lock {
+			if let Some(piece) = piece {
+				for (x, y) in piece_blocks(&piece) {
+					prev[index_of(x, y)] = kind as u8;
+				}
+			}
+			clear_lines(&mut prev);
+			if rise {
+				prev.copy_within(WIDTH.., 0);
+				for cell in prev[TOP * WIDTH..].iter_mut() {
+					*cell = 0;
+				}
+			}
+			if mirror {
+				for row in prev[..TOP * WIDTH].chunks_mut(WIDTH) {
+					row.reverse();
+				}
+			}
+		}
+	}
+
+	Ok(pages)
+}
+
+// Values
+
+struct Values {
+	data: Vec<u32>,
+	index: usize
+}
+
+impl Values {
+
+	fn is_empty(&self) -> bool {
+		self.index >= self.data.len()
+	}
+
+	/// Reads a number stored as `digits` base 64 digits (little endian)
+	fn poll(&mut self, digits: usize) -> Result<u32, FumenError> {
+
+		let mut value = 0;
+
+		for i in 0..digits {
+			let digit = self.data.get(self.index).ok_or(FumenError::InvalidData)?;
+			value += digit * 64_u32.pow(i as u32);
+			self.index += 1;
+		}
+
+		Ok(value)
+	}
+}
+
+fn push(values: &mut Vec<u32>, mut value: u32, digits: usize) {
+	for _ in 0..digits {
+		values.push(value % 64);
+		value /= 64;
+	}
+}
+
+fn diff_runs(prev: &Field, field: &Field) -> Vec<(u32, u32)> {
+
+	let mut runs: Vec<(u32, u32)> = vec![];
+
+	for (old, new) in prev.iter().zip(field.iter()) {
+		let diff = (*new as i32 - *old as i32 + 8) as u32;
+		match runs.last_mut() {
+			Some((last, count)) if *last == diff => *count += 1,
+			_ => runs.push((diff, 1))
+		}
+	}
+
+	runs
+}
+
+// Field
+
+/// Grid values use IJLOSTZ = 1..7, fumen uses I L O Z T J S = 1..7 (8 is garbage in both)
+fn to_fumen(cell: u8) -> u8 {
+	[0, 1, 6, 2, 3, 7, 5, 4, 8][cell as usize]
+}
+
+fn from_fumen(cell: u8) -> u8 {
+	[0, 1, 3, 4, 7, 6, 2, 5, 8][cell as usize]
+}
+
+fn index_of(x: i64, y: i64) -> usize {
+	(TOP as i64 - 1 - y) as usize * WIDTH + x as usize
+}
+
+/// Grid cell (row, column) of the fumen coordinates, if it is inside our playfield
+fn cell_of(x: i64, y: i64) -> Option<(usize, usize)> {
+	if (0..8).contains(&x) && (0..18).contains(&y) {
+		Some(((19 - y) as usize, (x + 2) as usize))
+	}
+	else {
+		None
+	}
+}
+
+fn field_from_board(board: &Board) -> Field {
+
+	let mut field = [0; BLOCKS];
+
+	for y in 0..18 {
+		for x in 0..8 {
+			let cell = cell_of(x, y).unwrap();
+			field[index_of(x, y)] = to_fumen(board.grid[cell]);
+		}
+	}
+
+	field
+}
+
+fn board_from_field(field: &Field) -> Result<Board, FumenError> {
+
+	let mut board = Board::new([0; 16]);
+
+	for (index, cell) in field.iter().enumerate().filter(|(_, cell)| **cell != 0) {
+		let x = (index % WIDTH) as i64;
+		let y = TOP as i64 - 1 - (index / WIDTH) as i64;
+		let cell_index = cell_of(x, y).ok_or(FumenError::OutOfBounds)?;
+		board.grid[cell_index] = from_fumen(*cell);
+	}
+
+	Ok(board)
+}
+
+fn clear_lines(field: &mut Field) {
+
+	let mut rows: Vec<[u8; WIDTH]> = field[..TOP * WIDTH]
+		.chunks(WIDTH)
+		.filter(|row| row.contains(&0))
+		.map(|row| { let mut r = [0; WIDTH]; r.copy_from_slice(row); r })
+		.collect();
+
+	while rows.len() < TOP {
+		rows.insert(0, [0; WIDTH]);
+	}
+
+	for (i, row) in rows.iter().enumerate() {
+		field[i * WIDTH..(i + 1) * WIDTH].copy_from_slice(row);
+	}
+}
+
+// Pieces
+
+/// Fumen rotations are Reverse, Right, Spawn, Left
+const ROTATIONS: [u32; 4] = [2, 1, 0, 3];
+
+/// Fumen locates pieces by an older convention than SRS for O, I, S and Z
+fn legacy_offset(shape: Shape) -> (i64, i64) {
+	match shape {
+		Shape::O(0) => (0, 1),
+		Shape::O(2) => (-1, 0),
+		Shape::O(3) => (-1, 1),
+		Shape::I(2) => (-1, 0),
+		Shape::I(3) => (0, 1),
+		Shape::S(0) => (0, 1),
+		Shape::S(1) => (1, 0),
+		Shape::Z(0) => (0, 1),
+		Shape::Z(3) => (-1, 0),
+		_ => (0, 0)
+	}
+}
+
+fn piece_blocks(piece: &Piece) -> Vec<(i64, i64)> {
+	piece.cells()
+		.iter()
+		.map(|(i, j)| (*j as i64 - 2, 19 - *i as i64))
+		.collect()
+}
+
+/// Piece type, rotation and position of the action
+fn encode_piece(piece: Piece) -> (u32, u32, u32) {
+	let (shape, x, y) = piece.srs_location();
+	let (dx, dy) = legacy_offset(shape);
+	let position = index_of(x + dx, y + dy) as u32;
+	(to_fumen(shape.index()) as u32, ROTATIONS[shape.rotation() as usize], position)
+}
+
+/// Piece of the action
+fn decode_piece(kind: u32, rotation: u32, position: u32) -> Result<Piece, FumenError> {
+
+	let shape = Shape::from_index(from_fumen(kind as u8))
+		.ok_or(FumenError::InvalidPiece)?
+		.with_rotation(ROTATIONS[rotation as usize] as u8);
+
+	let (dx, dy) = legacy_offset(shape);
+	let x = (position as usize % WIDTH) as i64 - dx;
+	let y = TOP as i64 - 1 - (position as usize / WIDTH) as i64 - dy;
+
+	Piece::from_srs_location(shape, x, y).ok_or(FumenError::OutOfBounds)
+}
+
+// Comments are stored with Javascript's `escape`
+
+fn escape(s: &str) -> String {
+
+	let mut escaped = String::new();
+
+	for unit in s.encode_utf16() {
+		match std::char::from_u32(unit as u32) {
+			Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+			_ if unit < 256 => escaped.push_str(&format!("%{:02X}", unit)),
+			_ => escaped.push_str(&format!("%u{:04X}", unit))
+		}
+	}
+
+	escaped
+}
+
+fn unescape(s: &str) -> String {
+
+	let mut units: Vec<u16> = vec![];
+	let chars: Vec<char> = s.chars().collect();
+	let mut i = 0;
+
+	while i < chars.len() {
+		let hex = |from: usize, len: usize| -> Option<u16> {
+			let digits: String = chars.get(from..from + len)?.iter().collect();
+			u16::from_str_radix(&digits, 16).ok()
+		};
+		if chars[i] == '%' && chars.get(i + 1) == Some(&'u') && hex(i + 2, 4).is_some() {
+			units.push(hex(i + 2, 4).unwrap());
+			i += 6;
+		}
+		else if chars[i] == '%' && hex(i + 1, 2).is_some() {
+			units.push(hex(i + 1, 2).unwrap());
+			i += 3;
+		}
+		else {
+			units.push(chars[i] as u16);
+			i += 1;
+		}
+	}
+
+	String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::board::{self, Board};
+
+	#[test]
+	fn fumen_empty_test() {
+		let mut page = Page::new(Board::new([0; 16]));
+		page.piece = false;
+		assert_eq!("v115@vhAAgH", encode(&[page]));
+
+		let pages = decode("v115@vhAAgH").unwrap();
+		assert_eq!(1, pages.len());
+		assert!(!pages[0].piece);
+		assert!(pages[0].comment.is_none());
+	}
+
+	#[test]
+	fn fumen_repeat_test() {
+		let mut page = Page::new(Board::new([0; 16]));
+		page.piece = false;
+		assert_eq!("v115@vhBAgHAAA", encode(&[page.clone(), page]));
+		assert_eq!(2, decode("v115@vhBAgHAAA").unwrap().len());
+	}
+
+	#[test]
+	fn fumen_round_trip_test() {
+
+		let mut first: Board = "
+			##########
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#..s.....#
+			#Z.ss....#
+			#ZZTsJJJ.#
+			##########
+		".parse().unwrap();
+
+		let mut pages = vec![Page::new(first.clone())];
+		pages[0].comment = Some("Opener: 50% ñ".into());
+
+		for _ in 0..3 {
+			while let Ok(piece) = board::down(&first, &first.current) {
+				first.current = piece;
+			}
+			first.place_current_piece();
+			first.remove_full_lines();
+			first.collector.next();
+			let shape = first.collector.get_current();
+			first.set_current(Piece::new(4, shape));
+			pages.push(Page::new(first.clone()));
+		}
+		pages.push(pages[3].clone());
+		pages[4].piece = false;
+
+		let data = encode(&pages);
+		let decoded = decode(&format!("https://fumen.zui.jp/?{}", data)).unwrap();
+
+		assert_eq!(pages.len(), decoded.len());
+		for (page, other) in pages.iter().zip(decoded.iter()) {
+			assert_eq!(page.piece, other.piece);
+			assert_eq!(page.comment, other.comment);
+			if page.piece {
+				assert_eq!(page.board.to_string(), other.board.to_string());
+			}
+			else {
+				assert!(page.board.grid == other.board.grid);
+			}
+		}
+	}
+
+	#[test]
+	fn fumen_piece_test() {
+		for index in 1..8 {
+			for rotation in 0..4 {
+				let shape = Shape::from_index(index).unwrap().with_rotation(rotation);
+				let piece = Piece { position: [4, 10].into(), shape };
+				let (kind, rotation, position) = encode_piece(piece);
+				let decoded = decode_piece(kind, rotation, position).unwrap();
+				let mut expected = piece.cells();
+				let mut cells = decoded.cells();
+				expected.sort();
+				cells.sort();
+				assert_eq!(expected, cells);
+			}
+		}
+	}
+
+	#[test]
+	fn fumen_external_test() {
+
+		// Diagrams written by hand from the v115 format, as the fumen editor makes them
+		let page = |data: &str, index: usize| {
+			let pages = decode(data).unwrap();
+			assert_eq!(data, encode(&pages));
+			pages[index].clone()
+		};
+		let cells = |data: &str| {
+			let mut cells = page(data, 0).board.current.cells();
+			cells.sort();
+			cells
+		};
+
+		// T spawn flat at the bottom from the second column, T pointing right against the wall, I lying at the left
+		assert_eq!(vec![(18, 4), (19, 3), (19, 4), (19, 5)], cells("v115@vhAVPJ"));
+		assert_eq!(vec![(17, 2), (18, 2), (18, 3), (19, 2)], cells("v115@vhANJJ"));
+		assert_eq!(vec![(19, 2), (19, 3), (19, 4), (19, 5)], cells("v115@vhAxOJ"));
+
+		// The T locks into the field of the next page
+		let expected: Board = "
+			##########
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#..T.....#
+			#.TTT....#
+			##########
+		".parse().unwrap();
+		assert!(page("v115@vhBVPJAAA", 1).board.grid == expected.grid);
+
+		// An I in the field, no piece
+		let field = page("v115@bhzhPeAgH", 0);
+		assert!(!field.piece);
+		assert_eq!([1, 1, 1, 1, 0], [2, 3, 4, 5, 6].map(|j| field.board.grid[(19, j)]));
+	}
+
+	#[test]
+	fn fumen_errors_test() {
+		assert_eq!(Some(FumenError::InvalidPrefix), decode("v110@vhAAgH").err());
+		assert_eq!(Some(FumenError::InvalidData), decode("v115@vh!AgH").err());
+		assert_eq!(Some(FumenError::InvalidData), decode("v115@vhAA").err());
+		// Full row at the bottom of a 10 wide field
+		assert_eq!(Some(FumenError::OutOfBounds), decode("v115@bhJ8JeAgH").err());
+		// Cell past garbage, and a multibyte character before the version
+		assert_eq!(Some(FumenError::InvalidData), decode("v115@bhw/SeAAA").err());
+		assert_eq!(Some(FumenError::InvalidPrefix), decode("é115@vhAAgH").err());
+	}
+
+	#[test]
+	fn fumen_wrap_test() {
+		let data: String = (0..89).map(|i| TABLE[i % 64] as char).collect();
+		assert_eq!(format!("v115@{}", &data[..41]), wrap(&data[..41]));
+		assert_eq!(format!("v115@{}", &data[..42]), wrap(&data[..42]));
+		assert_eq!(format!("v115@{}?{}", &data[..42], &data[42..43]), wrap(&data[..43]));
+		assert_eq!(format!("v115@{}?{}", &data[..42], &data[42..89]), wrap(&data));
+
+		// Nine isolated blocks fill 41 characters
+		let board: Board = "
+			##########
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#........#
+			#Z.......#
+			#.Z.Z.Z.Z#
+			#........#
+			#Z.Z.Z.Z.#
+			##########
+		".parse().unwrap();
+		let mut page = Page::new(board);
+		page.piece = false;
+		let data = encode(&[page]);
+		assert_eq!(5 + 41, data.len());
+		assert_eq!(1, decode(&data).unwrap().len());
+	}
+}
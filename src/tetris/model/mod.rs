@@ -3,6 +3,9 @@ extern crate na;
 extern crate ggez;
 extern crate rand;
 
+pub mod bindings;
 pub mod board;
+pub mod fumen;
 pub mod shape;
+pub mod stats;
 pub mod world;
@@ -1,15 +1,6 @@
 
-use ggez::graphics::Color;
 use na::DMatrix;
 
-#[derive(PartialEq, Clone, Copy)]
-pub enum Rotation {
-	Rotate0,
-	Rotate90,
-	Rotate180,
-	Rotate270
-}
-
 #[derive(PartialEq, Clone, Copy)]
 pub enum Shape { I(u8), J(u8), L(u8), O(u8), S(u8), T(u8), Z(u8) }
 
@@ -46,18 +37,6 @@ impl Shape {
 		}
 	}
 
-	pub fn color(self) -> Color {
-		match self {
-			Shape::I(_) => Color::from_rgb(249, 35, 56),
-			Shape::J(_) => Color::from_rgb(201, 115, 255),
-			Shape::L(_) => Color::from_rgb(28, 118, 188),
-			Shape::O(_) => Color::from_rgb(254, 227, 86),
-			Shape::S(_) => Color::from_rgb(83, 213, 4),
-			Shape::T(_) => Color::from_rgb(54, 224, 255),
-			Shape::Z(_) => Color::from_rgb(248, 147, 29),
-		}
-	}
-
 	pub fn rotate_clockwise(self) -> Self {
 		match self {
 			Shape::I(i) => Shape::I((i + 1) % 4),
@@ -70,6 +49,18 @@ impl Shape {
 		}
 	}
 
+	pub fn rotate_counterclockwise(self) -> Self {
+		match self {
+			Shape::I(i) => Shape::I((i + 3) % 4),
+			Shape::J(i) => Shape::J((i + 3) % 4),
+			Shape::L(i) => Shape::L((i + 3) % 4),
+			Shape::O(i) => Shape::O((i + 3) % 4),
+			Shape::S(i) => Shape::S((i + 3) % 4),
+			Shape::T(i) => Shape::T((i + 3) % 4),
+			Shape::Z(i) => Shape::Z((i + 3) % 4),
+		}
+	}
+
 	pub fn x(self) -> usize {
 		for (i, col) in self.value().column_iter().enumerate() {
 			for cell in col.iter() {
@@ -132,6 +123,88 @@ impl Shape {
 			_ => None
 		}
 	}
+
+	/// Inverse of `from_index`: the value the shape leaves in the grid
+	pub fn index(self) -> u8 {
+		match self {
+			Shape::I(_) => 1,
+			Shape::J(_) => 2,
+			Shape::L(_) => 3,
+			Shape::O(_) => 4,
+			Shape::S(_) => 5,
+			Shape::T(_) => 6,
+			Shape::Z(_) => 7,
+		}
+	}
+
+	pub fn rotation(self) -> u8 {
+		match self {
+			Shape::I(i) | Shape::J(i) | Shape::L(i) | Shape::O(i) |
+			Shape::S(i) | Shape::T(i) | Shape::Z(i) => i
+		}
+	}
+
+	/// Same shape in the rotation state specified (0 is the spawn state)
+	pub fn with_rotation(self, rotation: u8) -> Self {
+		match self {
+			Shape::I(_) => Shape::I(rotation % 4),
+			Shape::J(_) => Shape::J(rotation % 4),
+			Shape::L(_) => Shape::L(rotation % 4),
+			Shape::O(_) => Shape::O(rotation % 4),
+			Shape::S(_) => Shape::S(rotation % 4),
+			Shape::T(_) => Shape::T(rotation % 4),
+			Shape::Z(_) => Shape::Z(rotation % 4),
+		}
+	}
+
+	pub fn letter(self) -> char {
+		match self {
+			Shape::I(_) => 'I',
+			Shape::J(_) => 'J',
+			Shape::L(_) => 'L',
+			Shape::O(_) => 'O',
+			Shape::S(_) => 'S',
+			Shape::T(_) => 'T',
+			Shape::Z(_) => 'Z',
+		}
+	}
+
+	/// Offsets of the blocks from the SRS rotation centre (`y` pointing up),
+	/// as used by most external tools to locate a piece
+	pub fn srs_offsets(self) -> [(i64, i64); 4] {
+
+		let mut offsets = match self {
+			Shape::I(_) => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+			Shape::J(_) => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+			Shape::L(_) => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+			Shape::O(_) => [(0, 0), (1, 0), (0, 1), (1, 1)],
+			Shape::S(_) => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+			Shape::T(_) => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+			Shape::Z(_) => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+		};
+
+		for _ in 0..self.rotation() {
+			for offset in offsets.iter_mut() {
+				*offset = (offset.1, -offset.0);
+			}
+		}
+
+		offsets
+	}
+
+	/// Shape in spawn state from its (case insensitive) letter
+	pub fn from_letter(letter: char) -> Option<Self> {
+		match letter.to_ascii_uppercase() {
+			'I' => Some(Shape::I(0)),
+			'J' => Some(Shape::J(0)),
+			'L' => Some(Shape::L(0)),
+			'O' => Some(Shape::O(0)),
+			'S' => Some(Shape::S(0)),
+			'T' => Some(Shape::T(0)),
+			'Z' => Some(Shape::Z(0)),
+			_ => None
+		}
+	}
 }
 
 #[cfg(test)]
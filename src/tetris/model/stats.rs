@@ -0,0 +1,77 @@
+
+/// Points for clearing lines with a single piece (guideline scoring, level 1)
+pub fn points(lines: usize) -> usize {
+	match lines {
+		0 => 0,
+		1 => 100,
+		2 => 300,
+		3 => 500,
+		_ => 800
+	}
+}
+
+/// Garbage lines sent to the opponent for clearing lines with a single piece
+pub fn garbage(lines: usize) -> usize {
+	match lines {
+		0 | 1 => 0,
+		2 => 1,
+		3 => 2,
+		_ => 4
+	}
+}
+
+/// What a board did so far
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+	pub pieces: usize,
+	pub lines: usize,
+	pub score: usize,
+	pub seconds: f32,
+	/// Garbage lines sent to the other boards (once cancelled) and received from them in versus
+	pub sent: usize,
+	pub received: usize,
+	/// Place among the boards that topped out (1 for the first one)
+	pub topped_out: Option<usize>,
+	/// Last board standing in versus
+	pub winner: bool
+}
+
+impl Stats {
+
+	/// A piece locked clearing `lines` lines, scored at the level before the clear
+	pub fn lock(&mut self, lines: usize) {
+		self.score += points(lines) * self.level();
+		self.pieces += 1;
+		self.lines += lines;
+	}
+
+	/// Level, one more every ten lines
+	pub fn level(&self) -> usize {
+		1 + self.lines / 10
+	}
+
+	/// Pieces locked per second
+	pub fn pps(&self) -> f32 {
+		if self.seconds > 0.0 { self.pieces as f32 / self.seconds } else { 0.0 }
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	#[test]
+	fn stats_lock_test() {
+		let mut stats = Stats::default();
+		for lines in [0, 1, 4, 2].iter() {
+			stats.lock(*lines);
+		}
+		assert_eq!((4, 7, 1200), (stats.pieces, stats.lines, stats.score));
+
+		// Tetris at level 2
+		stats.lock(4);
+		stats.lock(4);
+		assert_eq!((2, 800 * 2), (stats.level(), stats.score - 2000));
+	}
+}
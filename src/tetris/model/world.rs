@@ -1,11 +1,117 @@
 
 use ggez::conf::Conf;
+use super::bindings::Bindings;
+use super::fumen::Page;
+use super::super::ai::bot::Dna;
+use super::super::ai::controller::Player;
+use super::super::view::theme::Theme;
+
+/// Seed from a number, so runs can be repeated from the command line
+pub fn seed_from(number: u64) -> [u8; 16] {
+	let bytes = number.to_le_bytes();
+	let mut seed = [0; 16];
+	seed[..8].copy_from_slice(&bytes);
+	seed[8..].copy_from_slice(&bytes);
+	seed
+}
+
+/// What ends a board besides topping out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+	/// Plays until topping out
+	Marathon,
+	/// Clears 40 lines
+	Sprint,
+	/// Scores for two minutes
+	Ultra,
+	/// Lines cleared send garbage to the other boards, the last one standing wins
+	Versus
+}
+
+impl Mode {
+
+	pub const ALL: [Mode; 4] = [Mode::Marathon, Mode::Sprint, Mode::Ultra, Mode::Versus];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Mode::Marathon => "Marathon",
+			Mode::Sprint => "Sprint (40 lines)",
+			Mode::Ultra => "Ultra (2 minutes)",
+			Mode::Versus => "Versus"
+		}
+	}
+
+	/// Lines ending the board, if any
+	pub fn lines(self) -> Option<usize> {
+		if self == Mode::Sprint { Some(40) } else { None }
+	}
+
+	/// Seconds ending the board, if any
+	pub fn seconds(self) -> Option<f32> {
+		if self == Mode::Ultra { Some(120.0) } else { None }
+	}
+}
+
+/// Rules chosen together from the menu
+pub struct Preset {
+	pub name: &'static str,
+	/// Frames the piece takes to fall one row
+	pub gravity: u32,
+	/// Pieces per second of the bots
+	pub pps: f32
+}
+
+pub const PRESETS: [Preset; 3] = [
+	Preset { name: "Relaxed", gravity: 60, pps: 1.0 },
+	Preset { name: "Classic", gravity: 30, pps: 2.0 },
+	Preset { name: "Fast", gravity: 10, pps: 4.0 }
+];
 
 #[derive(Clone)]
 pub struct World {
 	pub nrows: usize,
 	pub ncols: usize,
-	pub has_player: bool,
+	/// Who plays each board, row by row (repeated if there are fewer than boards)
+	pub players: Vec<Player>,
+	/// Pieces per second the bots play at
+	pub pps: f32,
 	pub config: Conf,
-	pub seed: [u8; 16]
+	/// Every board gets the pieces of this seed (see `seed_from`)
+	pub seed: u64,
+	pub mode: Mode,
+	/// Frames the pieces take to fall one row
+	pub gravity: u32,
+	/// Whether the game pauses when the window loses focus
+	pub pause_on_focus_loss: bool,
+	/// Position every board starts from (an empty board if none)
+	pub start: Option<Page>,
+	/// Weights of the bot reviewing the placements of the humans (no review if none)
+	pub coach: Option<Dna>,
+	/// Keys of the human boards
+	pub bindings: Bindings,
+	/// Colours and blocks of the game
+	pub theme: Theme
+}
+
+#[cfg(test)]
+impl World {
+
+	/// A single board with no player, classic rules and the default keys and theme
+	pub fn for_test() -> Self {
+		Self {
+			nrows: 1,
+			ncols: 1,
+			players: vec![],
+			pps: 2.0,
+			config: Conf::default(),
+			seed: 0,
+			mode: Mode::Marathon,
+			gravity: 30,
+			pause_on_focus_loss: true,
+			start: None,
+			coach: None,
+			bindings: Bindings::default(),
+			theme: Theme::default()
+		}
+	}
 }
@@ -1,56 +1,189 @@
 
-use ggez::{ContextBuilder, event, Context, GameResult, timer, graphics, quit};
-use ggez::event::{EventHandler, KeyMods, KeyCode};
-use ggez::graphics::{Rect, clear, MeshBuilder, Drawable, DrawParam};
-use super::subgame::SubGame;
-use super::subgame::Player;
-use super::super::model::world::World;
+use ggez::{event, Context, GameResult, timer, graphics, quit};
+use ggez::event::{EventHandler, EventsLoop, KeyMods, KeyCode, MouseButton};
+use ggez::graphics::{Image, Rect, clear, MeshBuilder, Drawable, DrawParam};
+use ggez::graphics::spritebatch::SpriteBatch;
+use super::pause::{Pause, Action};
+use super::subgame::{SubGame, FPS};
+use super::theme::{Painter, Style};
+use super::window;
+use super::super::model::bindings;
+use super::super::model::stats::Stats;
+use super::super::model::world::{World, Mode};
+
+/// Frames the final boards stay on screen before the game over scene
+const END_DELAY: u32 = 2 * FPS;
 
 pub struct Game {
 	pub world: World,
-	games: Vec<SubGame>
+	games: Vec<SubGame>,
+	/// Boards topped out so far
+	topped_out: usize,
+	/// Frames since the match ended
+	ended: Option<u32>,
+	/// Overlay of the paused game
+	pause: Option<Pause>,
+	/// Board enlarged to the whole window, and the size of the window
+	focus: Option<usize>,
+	size: (f32, f32),
+	/// Tile of the image style of the theme
+	tile: Option<Image>,
+	/// Whether the game should start again with the same seed
+	pub restart: bool,
+	/// Whether the window was closed (rather than the game ended)
+	pub closed: bool
 }
 
 impl Game {
-	pub fn new(world: World) -> Self {
+	/// Fails with the name of a bot that cannot be built
+	pub fn new(world: World) -> Result<Self, String> {
 
 		let mut games = vec![];
 
-		let col_offset = world.config.window_mode.width / world.ncols as f32;
-		let row_offset = world.config.window_mode.height / world.nrows as f32;
+		let (width, height) = (world.config.window_mode.width, world.config.window_mode.height);
 
 		for i in 0..world.nrows {
 			for j in  0..world.ncols {
-				let rect = Rect {
-					x: col_offset * j as f32,
-					y: row_offset * i as f32,
-					w: col_offset,
-					h: row_offset
-				};
-				if world.has_player && i == 0 && j == 0 {
-					games.push(SubGame::new(rect, world.seed, Player::Human));
-				}
-				else {
-					games.push(SubGame::new(rect, world.seed, Player::Bot));
-				}
+				let rect = Self::cell(&world, width, height, i, j);
+				let player = world.players[(i * world.ncols + j) % world.players.len()].clone();
+				let keys = games.iter().filter(|game: &&SubGame| game.is_human()).count();
+				games.push(SubGame::new(rect, &world, player, keys)?);
 			}
 		}
 
-		Self {
+		Ok(Self {
 			world,
-			games
+			games,
+			topped_out: 0,
+			ended: None,
+			pause: None,
+			focus: None,
+			size: (width, height),
+			tile: None,
+			restart: false,
+			closed: false
+		})
+	}
+
+	/// Part of a screen of `width` by `height` given to the board at row `i` and column `j`
+	fn cell(world: &World, width: f32, height: f32, i: usize, j: usize) -> Rect {
+		let col_offset = width / world.ncols as f32;
+		let row_offset = height / world.nrows as f32;
+		Rect {
+			x: col_offset * j as f32,
+			y: row_offset * i as f32,
+			w: col_offset,
+			h: row_offset
+		}
+	}
+
+	/// Lays the boards out again on a screen of `width` by `height`, the focused one on all of it
+	fn layout(&mut self, width: f32, height: f32) {
+		self.size = (width, height);
+		for (index, game) in self.games.iter_mut().enumerate() {
+			if self.focus == Some(index) {
+				game.resize(Rect::new(0.0, 0.0, width, height));
+			}
+			else {
+				game.resize(Self::cell(&self.world, width, height, index / self.world.ncols, index % self.world.ncols));
+			}
+		}
+	}
+
+	/// Enlarges a board, or goes back to the grid with `None`
+	fn set_focus(&mut self, focus: Option<usize>) {
+		self.focus = focus;
+		let (width, height) = self.size;
+		self.layout(width, height);
+	}
+
+	/// The board clicked in the grid gets the focus, a click on the focused board goes back to the grid
+	fn click(&mut self, x: f32, y: f32) {
+		match self.focus {
+			Some(_) => self.set_focus(None),
+			None => {
+				let index = self.games.iter().position(|game| game.contains(x, y));
+				self.set_focus(index);
+			}
+		}
+	}
+
+	/// Focuses the next board, after the last one goes back to the grid
+	fn cycle(&mut self) {
+		let next = self.focus.map_or(0, |index| index + 1);
+		self.set_focus(if next < self.games.len() { Some(next) } else { None });
+	}
+
+	/// Boards drawn: the focused one, or all of them
+	fn visible(&self) -> Vec<&SubGame> {
+		match self.focus {
+			Some(index) => vec![&self.games[index]],
+			None => self.games.iter().collect()
 		}
 	}
 
-	pub fn start(&mut self) {
+	/// Advances every board still playing one frame. Returns `true` once the
+	/// match has ended and its final boards have been shown long enough.
+	fn step(&mut self) -> bool {
 
-		let (ctx, event_loop) = &mut ContextBuilder::new("Tetris", "Mr.Robb")
-			.conf(self.world.config.clone())
-			.with_conf_file(true)
-			.build()
-			.expect(" ._. Could not create ggez context");
+		if let Some(frames) = &mut self.ended {
+			*frames += 1;
+			return *frames >= END_DELAY;
+		}
 
-		event::run(ctx, event_loop, self)
+		for game in &mut self.games {
+			game.update();
+			if game.is_topped_out() && game.stats.topped_out.is_none() {
+				self.topped_out += 1;
+				game.stats.topped_out = Some(self.topped_out);
+			}
+		}
+
+		// Garbage goes to the next board still playing
+		let count = self.games.len();
+		for index in 0..count {
+			let lines = self.games[index].take_sent();
+			let target = (1..count)
+				.map(|offset| (index + offset) % count)
+				.find(|target| !self.games[*target].is_finished());
+			if let (true, Some(target)) = (lines > 0, target) {
+				self.games[target].receive(lines);
+			}
+		}
+
+		// Every board is done, or all but one in versus
+		let versus = self.world.mode == Mode::Versus && count > 1;
+		let playing = self.games.iter().filter(|game| !game.is_finished()).count();
+		if playing == 0 || (versus && playing == 1) {
+			if let Some(index) = self.games.iter().position(|game| !game.is_finished()) {
+				leg::success(&format!("Board {} ({}) wins", index + 1, self.games[index].name), None, None);
+				self.games[index].stats.winner = true;
+			}
+			self.ended = Some(0);
+		}
+
+		false
+	}
+
+	/// Who played each board and how it went
+	pub fn results(&self) -> Vec<(String, Stats)> {
+		self.games.iter()
+			.map(|game| (game.name.to_string(), game.stats.clone()))
+			.collect()
+	}
+
+	pub fn start(&mut self, ctx: &mut Context, events_loop: &mut EventsLoop) {
+		let (width, height) = graphics::drawable_size(ctx);
+		self.layout(width, height);
+
+		// Bevelled blocks if the tile cannot be loaded
+		let theme = &self.world.theme;
+		if let (Style::Image, Some(tile)) = (theme.style, &theme.tile) {
+			self.tile = Image::new(ctx, format!("/{}", tile))
+				.map_err(|e| leg::warn(&format!("Could not load the tile `{}`: {}", tile, e), "Theme".into(), None))
+				.ok();
+		}
+		event::run(ctx, events_loop, self)
 			.expect("Dirty exit");
 	}
 }
@@ -59,13 +192,16 @@ impl EventHandler for Game {
 
 	fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
 
-		const FPS: u32 = 2;
-
+		// Frames due while paused are dropped, so the boards resume where they were
 		while timer::check_update_time(ctx, FPS) {
 
-			for game in &mut self.games {
-				game.update();
-			};
+			if self.pause.is_some() {
+				continue;
+			}
+
+			if self.step() {
+				quit(ctx);
+			}
 		}
 
 		Ok(())
@@ -73,33 +209,191 @@ impl EventHandler for Game {
 
 	fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
 
-		clear(ctx, (28.0 / 255.0, 28.0 / 255.0, 30.0 / 255.0, 1.0).into());
+		let theme = &self.world.theme;
+		clear(ctx, theme.background.color());
 
 		let mut builder = MeshBuilder::new();
+		let mut batch = self.tile.clone().map(SpriteBatch::new);
 
-		for game in &mut self.games {
-			game.draw(&mut builder);
+		let focused = self.focus.is_some();
+		{
+			let mut painter = Painter::new(&mut builder, theme, batch.as_mut(), self.tile.as_ref());
+			for game in self.visible() {
+				game.draw(&mut painter, focused);
+			}
 		}
 
 		let mesh = builder.build(ctx);
 
-		if mesh.is_ok() {
-			mesh.unwrap().draw(ctx, DrawParam::default())
+		if let Ok(mesh) = mesh {
+			mesh.draw(ctx, DrawParam::default())
 				.expect("Could not draw the mesh");
 		}
 		else {
 			quit(ctx);
 		}
 
+		// Tiles, then what goes over them
+		if let Some(batch) = &batch {
+			graphics::draw(ctx, batch, DrawParam::default())?;
+		}
+		let mut overlay = MeshBuilder::new();
+		for game in self.visible() {
+			game.draw_overlay(&mut overlay);
+		}
+		if self.visible().iter().any(|game| game.is_finished()) {
+			let mesh = overlay.build(ctx)?;
+			graphics::draw(ctx, &mesh, DrawParam::default())?;
+		}
+
+		for game in self.visible() {
+			game.draw_hud(ctx, theme, focused);
+		}
+		graphics::draw_queued_text(ctx, DrawParam::default())?;
+
+		for game in self.visible() {
+			game.draw_label(ctx, theme)?;
+		}
+
+		if let Some(pause) = &self.pause {
+			pause.draw(ctx, &self.world)?;
+		}
+
 		graphics::present(ctx)
 			.expect("Could not present the scene");
 
 		Ok(())
 	}
 
-	fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+	fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods, repeat: bool) {
+
+		if let Some(pause) = &mut self.pause {
+			match pause.key_down(keycode, &mut self.world) {
+				Action::Stay => (),
+				Action::Resume => self.pause = None,
+				Action::Restart => {
+					self.restart = true;
+					quit(ctx);
+				}
+				Action::Quit => quit(ctx)
+			}
+			for game in &mut self.games {
+				game.set_pps(self.world.pps);
+				game.set_handling(&self.world.bindings);
+			}
+			return;
+		}
+
+		if !repeat && self.world.bindings.players.iter().any(|map| map.action(keycode) == Some(bindings::Action::Pause)) {
+			self.pause = Some(Pause::new());
+			return;
+		}
+
+		// Tab goes through the boards one by one then back to the grid, Backspace goes back at once
+		match keycode {
+			KeyCode::Tab => {
+				self.cycle();
+				return;
+			}
+			KeyCode::Back => {
+				self.set_focus(None);
+				return;
+			}
+			_ => ()
+		}
+
 		for game in &mut self.games {
-			game.key_down_event(ctx, keycode, keymods, repeat);
+			game.key_down_event(keycode, &self.world.bindings, repeat);
 		}
 	}
+
+	fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods) {
+		for game in &mut self.games {
+			game.key_up_event(keycode, &self.world.bindings);
+		}
+	}
+
+	fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+		if button == MouseButton::Left && self.pause.is_none() {
+			self.click(x, y);
+		}
+	}
+
+	fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+		window::fit(ctx, width, height);
+		self.layout(width, height);
+	}
+
+	fn focus_event(&mut self, _ctx: &mut Context, gained: bool) {
+		if !gained && self.world.pause_on_focus_loss && self.pause.is_none() {
+			self.pause = Some(Pause::new());
+		}
+	}
+
+	fn quit_event(&mut self, _ctx: &mut Context) -> bool {
+		self.closed = true;
+		false
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::super::ai::bot::default_dna;
+	use super::super::super::ai::controller::{Player, Strategy};
+
+	#[test]
+	fn game_versus_test() {
+		let world = World {
+			ncols: 2,
+			players: vec![Player::Bot(Strategy::Heuristic(default_dna())), Player::Bot(Strategy::Greedy)],
+			pps: 30.0,
+			seed: 3,
+			mode: Mode::Versus,
+			gravity: 1,
+			..World::for_test()
+		};
+		let mut game = Game::new(world).unwrap();
+
+		// The match ends within ten minutes of play
+		let ended = (0..10 * 60 * FPS).any(|_| game.step());
+		assert!(ended);
+
+		// One board stands, and garbage went from each board to the other
+		let results = game.results();
+		let winners: Vec<&Stats> = results.iter().map(|(_, stats)| stats).filter(|stats| stats.winner).collect();
+		assert_eq!(1, winners.len());
+		assert!(winners[0].topped_out.is_none());
+		assert_eq!(1, results.iter().filter(|(_, stats)| stats.topped_out == Some(1)).count());
+		for (from, to) in [(0, 1), (1, 0)].iter() {
+			assert!(results[*to].1.received > 0 && results[*to].1.received <= results[*from].1.sent);
+		}
+	}
+
+	#[test]
+	fn game_focus_test() {
+		let world = World {
+			nrows: 2,
+			ncols: 2,
+			players: vec![Player::Bot(Strategy::Greedy)],
+			..World::for_test()
+		};
+		let mut game = Game::new(world).unwrap();
+		game.layout(800.0, 600.0);
+
+		// The board at the bottom right takes the whole window, then Tab wraps around to the grid
+		game.click(600.0, 450.0);
+		assert_eq!(Some(3), game.focus);
+		assert!(game.games[3].contains(400.0, 20.0) && game.visible().len() == 1);
+		game.click(10.0, 10.0);
+		assert_eq!((None, 4), (game.focus, game.visible().len()));
+		assert!(!game.games[3].contains(400.0, 20.0));
+
+		game.cycle();
+		assert_eq!(Some(0), game.focus);
+		game.set_focus(Some(3));
+		game.cycle();
+		assert_eq!(None, game.focus);
+	}
 }
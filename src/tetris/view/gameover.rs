@@ -1,14 +1,152 @@
-
+use ggez::{event, Context, GameResult, graphics, quit};
+use ggez::event::{EventHandler, EventsLoop, KeyMods, KeyCode};
+use ggez::graphics::{Color, DrawParam, Scale, Text, TextFragment};
+use super::super::model::stats::Stats;
 use super::super::model::world::World;
+use super::window;
+
+/// Where to go from the game over screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Choice {
+	/// Same pieces again
+	Restart,
+	/// Other pieces
+	NewSeed,
+	Menu,
+	Quit
+}
+
+const CHOICES: [(Choice, &str); 4] = [
+	(Choice::Restart, "Play again (same seed)"),
+	(Choice::NewSeed, "Play again (new seed)"),
+	(Choice::Menu, "Menu"),
+	(Choice::Quit, "Quit")
+];
 
 pub struct GameOver {
-	pub world: World
+	pub world: World,
+	/// Who played each board and how it went
+	pub results: Vec<(String, Stats)>,
+	selected: usize,
+	/// What was chosen (quitting if the window is closed)
+	pub choice: Choice
 }
 
 impl GameOver {
-	pub fn new(world: World) -> Self {
+
+	pub fn new(world: World, results: Vec<(String, Stats)>) -> Self {
+
+		for (index, (name, stats)) in results.iter().enumerate() {
+			leg::info(&format!(
+				"Board {} ({}): {} lines, {} points, {} pieces in {:.1}s{}",
+				index + 1, name, stats.lines, stats.score, stats.pieces, stats.seconds,
+				stats.topped_out.map_or(String::new(), |rank| format!(", topped out #{}", rank))
+			), "Game over".into(), None);
+		}
+
 		Self {
-			world
+			world,
+			results,
+			selected: 0,
+			choice: Choice::Quit
+		}
+	}
+
+	/// Shows the results until a choice is made
+	pub fn start(&mut self, ctx: &mut Context, events_loop: &mut EventsLoop) -> Choice {
+		event::run(ctx, events_loop, self)
+			.expect("Dirty exit");
+		self.choice
+	}
+}
+
+fn time(seconds: f32) -> String {
+	format!("{}:{:04.1}", (seconds / 60.0) as u32, seconds % 60.0)
+}
+
+impl EventHandler for GameOver {
+
+	fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+		Ok(())
+	}
+
+	fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+		window::fit(ctx, width, height);
+	}
+
+	fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+
+		let theme = &self.world.theme;
+		graphics::clear(ctx, theme.background.color());
+
+		let (w, h) = graphics::drawable_size(ctx);
+		let size = h / 30.0;
+		let white = theme.text.color();
+		let faded = theme.faded();
+		let highlight = theme.highlight.color();
+
+		let line = |ctx: &mut Context, content: String, scale: f32, color: Color, y: f32| -> GameResult<()> {
+			let text = Text::new(TextFragment::new(content).scale(Scale::uniform(scale)).color(color));
+			let x = (w - text.width(ctx) as f32) / 2.0;
+			graphics::draw(ctx, &text, DrawParam::default().dest([x, y]))
+		};
+
+		line(ctx, "GAME OVER".into(), size * 2.0, white, size * 2.0)?;
+
+		// Results table, a column every tenth of the width
+		const HEADERS: [&str; 7] = ["Board", "Player", "Lines", "Score", "Pieces", "Time", "Topped out"];
+		let cell = |ctx: &mut Context, content: String, column: usize, color: Color, y: f32| -> GameResult<()> {
+			let text = Text::new(TextFragment::new(content).scale(Scale::uniform(size * 0.8)).color(color));
+			graphics::draw(ctx, &text, DrawParam::default().dest([w * (0.15 + column as f32 * 0.1), y]))
+		};
+
+		let mut y = size * 5.5;
+		for (column, header) in HEADERS.iter().enumerate() {
+			cell(ctx, header.to_string(), column, faded, y)?;
+		}
+		for (index, (name, stats)) in self.results.iter().enumerate().take(16) {
+			y += size * 1.2;
+			let row = [
+				(index + 1).to_string(),
+				name.clone(),
+				stats.lines.to_string(),
+				stats.score.to_string(),
+				stats.pieces.to_string(),
+				time(stats.seconds),
+				stats.topped_out.map_or("-".to_string(), |rank| format!("#{}", rank))
+			];
+			for (column, content) in row.iter().enumerate() {
+				cell(ctx, content.clone(), column, white, y)?;
+			}
+		}
+		if self.results.len() > 16 {
+			y += size * 1.2;
+			line(ctx, format!("... and {} more boards", self.results.len() - 16), size * 0.8, faded, y)?;
+		}
+
+		y += size * 2.0;
+		for (index, (_, label)) in CHOICES.iter().enumerate() {
+			let selected = index == self.selected;
+			let content = if selected { format!("> {} <", label) } else { label.to_string() };
+			line(ctx, content, size, if selected { highlight } else { white }, y + index as f32 * size * 1.5)?;
+		}
+
+		graphics::present(ctx)
+	}
+
+	fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+		match keycode {
+			KeyCode::Up => self.selected = (self.selected + CHOICES.len() - 1) % CHOICES.len(),
+			KeyCode::Down => self.selected = (self.selected + 1) % CHOICES.len(),
+			KeyCode::Return | KeyCode::NumpadEnter => {
+				self.choice = CHOICES[self.selected].0;
+				quit(ctx);
+			}
+			KeyCode::Escape => {
+				self.choice = Choice::Menu;
+				quit(ctx);
+			}
+			_ => ()
 		}
 	}
 }
@@ -1,14 +1,301 @@
+use ggez::{event, Context, GameResult, graphics, quit};
+use ggez::event::{EventHandler, EventsLoop, KeyMods, KeyCode};
+use ggez::graphics::{Color, DrawParam, Scale, Text, TextFragment};
+use super::super::ai::bot::{Dna, default_dna};
+use super::super::ai::controller::{Player, Strategy};
+use super::super::model::world::{World, Mode, PRESETS};
+use super::theme::Theme;
+use super::window;
 
-use super::super::model::world::World;
+const MAX_ROWS: usize = 6;
+const MAX_COLS: usize = 10;
+
+/// Bots of the boards not played by a human
+const BOTS: [&str; 4] = ["as given", "heuristic", "greedy", "random"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Item {
+	Mode,
+	Rows,
+	Columns,
+	Player,
+	Bots,
+	Seed,
+	Rules,
+	Speed,
+	Coach,
+	Theme,
+	Start
+}
+
+const ITEMS: [Item; 11] = [
+	Item::Mode, Item::Rows, Item::Columns, Item::Player, Item::Bots,
+	Item::Seed, Item::Rules, Item::Speed, Item::Coach, Item::Theme, Item::Start
+];
 
 pub struct Menu {
-	pub world: World
+	pub world: World,
+	/// Players given on the command line
+	given: Vec<Player>,
+	selected: usize,
+	/// Boards played from the keyboard, each with its own key map
+	humans: usize,
+	bots: usize,
+	preset: usize,
+	/// Weights of the coach when turned on
+	coach: Dna,
+	/// Themes to choose from: the built-in ones, and the one given if it is not
+	themes: Vec<Theme>,
+	/// Whether the menu was left to start a game
+	started: bool
 }
 
 impl Menu {
+
 	pub fn new(world: World) -> Self {
+
+		let mut themes = Theme::builtin();
+		if !themes.contains(&world.theme) {
+			themes.push(world.theme.clone());
+		}
+
 		Self {
+			themes,
+			given: world.players.clone(),
+			selected: ITEMS.len() - 1,
+			humans: world.players.iter().filter(|player| matches!(player, Player::Human)).count().min(world.bindings.players.len()),
+			bots: 0,
+			preset: PRESETS.iter().position(|preset| preset.gravity == world.gravity).unwrap_or(1),
+			coach: world.coach.clone().unwrap_or_else(default_dna),
+			started: false,
 			world
 		}
 	}
+
+	/// Shows the menu until a game is started (`true`) or the window closed
+	pub fn start(&mut self, ctx: &mut Context, events_loop: &mut EventsLoop) -> bool {
+		event::run(ctx, events_loop, self)
+			.expect("Dirty exit");
+		self.started
+	}
+
+	fn label(&self, item: Item) -> String {
+		match item {
+			Item::Mode => format!("Mode: {}", self.world.mode.name()),
+			Item::Rows => format!("Rows: {}", self.world.nrows),
+			Item::Columns => format!("Columns: {}", self.world.ncols),
+			Item::Player => match self.humans {
+				0 => "Players: spectating".into(),
+				humans => format!("Players: {} human{}", humans, if humans > 1 { "s" } else { "" })
+			},
+			Item::Bots => format!("Bots: {}", BOTS[self.bots]),
+			Item::Seed => format!("Seed: {}", self.world.seed),
+			Item::Rules => format!("Rules: {}", PRESETS[self.preset].name),
+			Item::Speed => format!("Bot speed: {:.1} pieces per second", self.world.pps),
+			Item::Coach => format!("Coach: {}", if self.world.coach.is_some() { "on" } else { "off" }),
+			Item::Theme => format!("Theme: {}", self.world.theme.name),
+			Item::Start => "Start".into()
+		}
+	}
+
+	/// Left (-1) or right (+1) on an item
+	fn change(&mut self, item: Item, step: i64) {
+
+		let cycle = |index: usize, len: usize| (index as i64 + step).rem_euclid(len as i64) as usize;
+		let clamp = |value: usize, max: usize| (value as i64 + step).max(1).min(max as i64) as usize;
+
+		match item {
+			Item::Mode => {
+				let index = Mode::ALL.iter().position(|mode| *mode == self.world.mode).unwrap_or(0);
+				self.world.mode = Mode::ALL[cycle(index, Mode::ALL.len())];
+			}
+			Item::Rows => self.world.nrows = clamp(self.world.nrows, MAX_ROWS),
+			Item::Columns => self.world.ncols = clamp(self.world.ncols, MAX_COLS),
+			Item::Player => self.humans = cycle(self.humans, self.world.bindings.players.len() + 1),
+			Item::Bots => self.bots = cycle(self.bots, BOTS.len()),
+			Item::Seed => self.world.seed = self.world.seed.wrapping_add(step as u64),
+			Item::Rules => {
+				self.preset = cycle(self.preset, PRESETS.len());
+				self.world.gravity = PRESETS[self.preset].gravity;
+				self.world.pps = PRESETS[self.preset].pps;
+			}
+			Item::Speed => self.world.pps = (self.world.pps + step as f32 * 0.5).max(0.5),
+			Item::Coach => {
+				self.world.coach = match self.world.coach {
+					Some(_) => None,
+					None => Some(self.coach.clone())
+				};
+			}
+			Item::Theme => {
+				let index = self.themes.iter().position(|theme| *theme == self.world.theme).unwrap_or(0);
+				self.world.theme = self.themes[cycle(index, self.themes.len())].clone();
+			}
+			Item::Start => ()
+		}
+	}
+
+	/// Players of the boards chosen, row by row
+	fn players(&self) -> Vec<Player> {
+
+		let mut bots: Vec<Player> = match BOTS[self.bots] {
+			"heuristic" => vec![Player::Bot(Strategy::Heuristic(self.coach.clone()))],
+			"greedy" => vec![Player::Bot(Strategy::Greedy)],
+			"random" => vec![Player::Bot(Strategy::Random)],
+			_ => self.given.iter().filter(|player| matches!(player, Player::Bot(_))).cloned().collect()
+		};
+		if bots.is_empty() {
+			bots.push(Player::Bot(Strategy::Heuristic(default_dna())));
+		}
+
+		// The humans given keep their boards when the bots are the ones given
+		let given = BOTS[self.bots] == "as given" && self.given.iter().any(|player| matches!(player, Player::Human));
+		let boards = self.world.nrows * self.world.ncols;
+		let mut players: Vec<Player> = if given {
+			(0..boards).map(|i| self.given[i % self.given.len()].clone()).collect()
+		}
+		else {
+			(0..boards).map(|i| bots[i % bots.len()].clone()).collect()
+		};
+
+		// The humans in place are kept up to the number chosen, then the first bots make way
+		let mut bots = bots.iter().cycle();
+		let mut humans = 0;
+		for player in players.iter_mut().filter(|player| matches!(player, Player::Human)) {
+			if humans < self.humans {
+				humans += 1;
+			}
+			else {
+				*player = bots.next().unwrap().clone();
+			}
+		}
+		for player in players.iter_mut().filter(|player| matches!(player, Player::Bot(_))).take(self.humans - humans) {
+			*player = Player::Human;
+		}
+
+		players
+	}
+
+	fn confirm(&mut self, ctx: &mut Context) {
+		self.world.players = self.players();
+		self.started = true;
+		quit(ctx);
+	}
+}
+
+impl EventHandler for Menu {
+
+	fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+		Ok(())
+	}
+
+	fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+		window::fit(ctx, width, height);
+	}
+
+	fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+
+		let theme = &self.world.theme;
+		graphics::clear(ctx, theme.background.color());
+
+		let (w, h) = graphics::drawable_size(ctx);
+		let size = h / 24.0;
+		let white = theme.text.color();
+		let faded = theme.faded();
+		let highlight = theme.highlight.color();
+
+		let line = |ctx: &mut Context, content: String, scale: f32, color: Color, y: f32| -> GameResult<()> {
+			let text = Text::new(TextFragment::new(content).scale(Scale::uniform(scale)).color(color));
+			let x = (w - text.width(ctx) as f32) / 2.0;
+			graphics::draw(ctx, &text, DrawParam::default().dest([x, y]))
+		};
+
+		line(ctx, "TETRIS".into(), size * 2.0, white, size * 2.0)?;
+
+		for (index, item) in ITEMS.iter().enumerate() {
+			let selected = index == self.selected;
+			let content = if selected { format!("> {} <", self.label(*item)) } else { self.label(*item) };
+			line(ctx, content, size, if selected { highlight } else { white }, size * (5.5 + index as f32 * 1.5))?;
+		}
+
+		line(
+			ctx,
+			"Up/Down choose, Left/Right change, type the seed (R: random), Enter starts, Esc quits".into(),
+			size * 0.6, faded, h - size * 2.0
+		)?;
+
+		graphics::present(ctx)
+	}
+
+	fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+
+		let item = ITEMS[self.selected];
+
+		match keycode {
+			KeyCode::Up => self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len(),
+			KeyCode::Down => self.selected = (self.selected + 1) % ITEMS.len(),
+			KeyCode::Left => self.change(item, -1),
+			KeyCode::Right => self.change(item, 1),
+			KeyCode::Return | KeyCode::NumpadEnter => self.confirm(ctx),
+			KeyCode::Back if item == Item::Seed => self.world.seed /= 10,
+			KeyCode::R if item == Item::Seed => self.world.seed = rand::random(),
+			KeyCode::Escape => quit(ctx),
+			_ => ()
+		}
+	}
+
+	fn text_input_event(&mut self, _ctx: &mut Context, character: char) {
+		if ITEMS[self.selected] == Item::Seed {
+			if let Some(digit) = character.to_digit(10) {
+				self.world.seed = self.world.seed.saturating_mul(10).saturating_add(digit as u64);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	fn world(players: Vec<Player>) -> World {
+		World {
+			ncols: 2,
+			players,
+			seed: 7,
+			..World::for_test()
+		}
+	}
+
+	#[test]
+	fn menu_players_test() {
+		let mut menu = Menu::new(world(vec![Player::Human, Player::Bot(Strategy::Greedy)]));
+		assert_eq!(Item::Start, ITEMS[menu.selected]);
+
+		menu.change(Item::Rows, 1);
+		menu.change(Item::Columns, -5);
+		assert_eq!((2, 1), (menu.world.nrows, menu.world.ncols));
+
+		// As given: the human keeps the first board
+		let players = menu.players();
+		assert!(matches!(players[0], Player::Human));
+		assert!(matches!(players[1], Player::Bot(Strategy::Greedy)));
+
+		// Two local players
+		menu.change(Item::Player, 1);
+		assert!(menu.players().iter().all(|player| matches!(player, Player::Human)));
+
+		// Spectating with random bots
+		menu.change(Item::Player, 1);
+		menu.change(Item::Bots, -1);
+		assert!(menu.players().iter().all(|player| matches!(player, Player::Bot(Strategy::Random))));
+
+		menu.change(Item::Rules, 1);
+		assert_eq!((10, 4.0), (menu.world.gravity, menu.world.pps));
+		menu.change(Item::Mode, -1);
+		assert_eq!(Mode::Versus, menu.world.mode);
+		menu.change(Item::Coach, 1);
+		assert!(menu.world.coach.is_some());
+		menu.change(Item::Theme, -1);
+		assert_eq!("Tiles", menu.world.theme.name);
+	}
 }
@@ -2,6 +2,8 @@
 pub mod game;
 pub mod gameover;
 pub mod menu;
+pub mod pause;
 pub mod scene;
 pub mod subgame;
+pub mod theme;
 pub mod window;
@@ -0,0 +1,262 @@
+use ggez::{Context, GameResult, graphics};
+use ggez::event::KeyCode;
+use ggez::graphics::{Color, DrawMode, DrawParam, MeshBuilder, Rect, Scale, Text, TextFragment};
+use std::iter;
+use super::super::model::bindings::{self, Key};
+use super::super::model::world::World;
+
+/// What the game does after a key in the pause overlay
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+	Stay,
+	Resume,
+	/// Same seed, from the start
+	Restart,
+	/// Ends the match (to the game over scene)
+	Quit
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Item {
+	Resume,
+	Restart,
+	Settings,
+	Quit,
+	// Settings page
+	Speed,
+	PauseOnFocusLoss,
+	Controls,
+	Back,
+	// Controls page
+	Player,
+	Bind(bindings::Action),
+	Das,
+	Arr,
+	SoftDropFactor,
+	Save
+}
+
+const ITEMS: [Item; 4] = [Item::Resume, Item::Restart, Item::Settings, Item::Quit];
+const SETTINGS: [Item; 4] = [Item::Speed, Item::PauseOnFocusLoss, Item::Controls, Item::Back];
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+enum Screen {
+	#[default]
+	Main,
+	Settings,
+	Controls
+}
+
+/// Overlay shown while the game is paused
+#[derive(Default)]
+pub struct Pause {
+	selected: usize,
+	screen: Screen,
+	/// Key map shown on the controls page
+	player: usize,
+	/// Action waiting for its new key
+	waiting: Option<bindings::Action>,
+	/// Outcome of the last save
+	status: Option<String>
+}
+
+impl Pause {
+
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn items(&self) -> Vec<Item> {
+		match self.screen {
+			Screen::Main => ITEMS.to_vec(),
+			Screen::Settings => SETTINGS.to_vec(),
+			Screen::Controls => iter::once(Item::Player)
+				.chain(bindings::Action::ALL.iter().map(|action| Item::Bind(*action)))
+				.chain(vec![Item::Das, Item::Arr, Item::SoftDropFactor, Item::Save, Item::Back])
+				.collect()
+		}
+	}
+
+	fn label(&self, item: Item, world: &World) -> String {
+		match item {
+			Item::Resume => "Resume".into(),
+			Item::Restart => "Restart".into(),
+			Item::Settings => "Settings".into(),
+			Item::Quit => "Quit".into(),
+			Item::Speed => format!("Bot speed: {:.1} pieces per second", world.pps),
+			Item::PauseOnFocusLoss => format!("Pause when the window loses focus: {}", if world.pause_on_focus_loss { "on" } else { "off" }),
+			Item::Controls => "Controls".into(),
+			Item::Back => "Back".into(),
+			Item::Player => format!("Player {} of {}", self.player + 1, world.bindings.players.len()),
+			Item::Bind(action) if self.waiting == Some(action) => format!("{}: press a key (Esc cancels)", action.name()),
+			Item::Bind(action) => {
+				let keys: Vec<String> = world.bindings.map(self.player).keys(action).iter().map(|key| key.to_string()).collect();
+				format!("{}: {}", action.name(), if keys.is_empty() { "-".into() } else { keys.join(", ") })
+			}
+			Item::Das => format!("Auto shift after {} frames", world.bindings.map(self.player).handling.das),
+			Item::Arr => match world.bindings.map(self.player).handling.arr {
+				0 => "Then straight to the wall".into(),
+				arr => format!("Then a move every {} frames", arr)
+			},
+			Item::SoftDropFactor => match world.bindings.map(self.player).handling.soft_drop_factor {
+				0 => "Soft drop: instant".into(),
+				factor => format!("Soft drop: {} times gravity", factor)
+			},
+			Item::Save => match &world.bindings.path {
+				Some(path) => format!("Save to {}", path.display()),
+				None => "Save".into()
+			}
+		}
+	}
+
+	/// Opens a page of the overlay, on its item `selected`
+	fn open(&mut self, screen: Screen, selected: usize) {
+		self.screen = screen;
+		self.selected = selected;
+		self.status = None;
+	}
+
+	/// Navigates the overlay, changing the settings of the world in place
+	pub fn key_down(&mut self, keycode: KeyCode, world: &mut World) -> Action {
+
+		// The key pressed goes to the action chosen
+		if let Some(action) = self.waiting.take() {
+			if keycode != KeyCode::Escape && Key::is_bindable(keycode) {
+				world.bindings.bind(self.player, action, keycode);
+			}
+			return Action::Stay;
+		}
+
+		let items = self.items();
+		let item = items[self.selected];
+		let players = world.bindings.players.len();
+
+		match keycode {
+			KeyCode::Up => self.selected = (self.selected + items.len() - 1) % items.len(),
+			KeyCode::Down => self.selected = (self.selected + 1) % items.len(),
+			KeyCode::Left | KeyCode::Right if item == Item::Speed => {
+				let step = if keycode == KeyCode::Left { -0.5 } else { 0.5 };
+				world.pps = (world.pps + step).max(0.5);
+			}
+			KeyCode::Left | KeyCode::Right if item == Item::PauseOnFocusLoss => {
+				world.pause_on_focus_loss = !world.pause_on_focus_loss;
+			}
+			KeyCode::Left | KeyCode::Right if item == Item::Player => {
+				let step = if keycode == KeyCode::Left { players - 1 } else { 1 };
+				self.player = (self.player + step) % players;
+			}
+			KeyCode::Left | KeyCode::Right if matches!(item, Item::Das | Item::Arr | Item::SoftDropFactor) => {
+				let handling = &mut world.bindings.map_mut(self.player).handling;
+				let value = match item {
+					Item::Das => &mut handling.das,
+					Item::Arr => &mut handling.arr,
+					_ => &mut handling.soft_drop_factor
+				};
+				*value = if keycode == KeyCode::Left { value.saturating_sub(1) } else { *value + 1 };
+			}
+			KeyCode::Escape | KeyCode::P if self.screen == Screen::Controls => self.open(Screen::Settings, 2),
+			KeyCode::Escape | KeyCode::P if self.screen == Screen::Settings => self.open(Screen::Main, 2),
+			KeyCode::Escape | KeyCode::P => return Action::Resume,
+			KeyCode::Return | KeyCode::NumpadEnter => match item {
+				Item::Resume => return Action::Resume,
+				Item::Restart => return Action::Restart,
+				Item::Quit => return Action::Quit,
+				Item::Settings => self.open(Screen::Settings, 0),
+				Item::PauseOnFocusLoss => world.pause_on_focus_loss = !world.pause_on_focus_loss,
+				Item::Controls => self.open(Screen::Controls, 0),
+				Item::Back if self.screen == Screen::Controls => self.open(Screen::Settings, 2),
+				Item::Back => self.open(Screen::Main, 2),
+				Item::Bind(action) => self.waiting = Some(action),
+				Item::Save => {
+					self.status = Some(match world.bindings.save() {
+						Ok(()) => "Saved".into(),
+						Err(e) => format!("Could not save: {}", e)
+					});
+				}
+				Item::Speed | Item::Player | Item::Das | Item::Arr | Item::SoftDropFactor => ()
+			},
+			_ => ()
+		}
+
+		Action::Stay
+	}
+
+	pub fn draw(&self, ctx: &mut Context, world: &World) -> GameResult<()> {
+
+		let (w, h) = graphics::drawable_size(ctx);
+		let size = h / 24.0;
+		let white = world.theme.text.color();
+		let faded = world.theme.faded();
+		let highlight = world.theme.highlight.color();
+
+		// Dims the boards
+		let mut builder = MeshBuilder::new();
+		builder.rectangle(DrawMode::fill(), Rect::new(0.0, 0.0, w, h), Color::new(0.0, 0.0, 0.0, 0.6));
+		let mesh = builder.build(ctx)?;
+		graphics::draw(ctx, &mesh, DrawParam::default())?;
+
+		let line = |ctx: &mut Context, content: String, scale: f32, color: Color, y: f32| -> GameResult<()> {
+			let text = Text::new(TextFragment::new(content).scale(Scale::uniform(scale)).color(color));
+			let x = (w - text.width(ctx) as f32) / 2.0;
+			graphics::draw(ctx, &text, DrawParam::default().dest([x, y]))
+		};
+
+		// The title and the items, in the middle of the screen
+		let items = self.items();
+		let top = (h - (items.len() as f32 * 1.5 + 3.5) * size) / 2.0;
+		let title = match self.screen {
+			Screen::Main => "PAUSED",
+			Screen::Settings => "SETTINGS",
+			Screen::Controls => "CONTROLS"
+		};
+		line(ctx, title.into(), size * 2.0, white, top)?;
+
+		for (index, item) in items.iter().enumerate() {
+			let selected = index == self.selected;
+			let label = self.label(*item, world);
+			let content = if selected { format!("> {} <", label) } else { label };
+			line(ctx, content, size, if selected { highlight } else { white }, top + size * 3.5 + index as f32 * size * 1.5)?;
+		}
+
+		if let Some(status) = &self.status {
+			line(ctx, status.clone(), size * 0.6, faded, h - size * 2.0)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	#[test]
+	fn pause_settings_test() {
+		let mut world = World::for_test();
+		let mut pause = Pause::new();
+
+		// Settings, bot speed up, focus loss off, back to the pause menu
+		for key in [KeyCode::Down, KeyCode::Down, KeyCode::Return, KeyCode::Right, KeyCode::Down, KeyCode::Return, KeyCode::Escape].iter() {
+			assert_eq!(Action::Stay, pause.key_down(*key, &mut world));
+		}
+		assert_eq!((2.5, false), (world.pps, world.pause_on_focus_loss));
+
+		// Controls of the second player: Space joins M for the hard drop, and leaves the first player
+		for key in [KeyCode::Return, KeyCode::Down, KeyCode::Down, KeyCode::Return, KeyCode::Right].iter() {
+			pause.key_down(*key, &mut world);
+		}
+		for key in [KeyCode::Down, KeyCode::Down, KeyCode::Down, KeyCode::Down, KeyCode::Return, KeyCode::Space].iter() {
+			assert_eq!(Action::Stay, pause.key_down(*key, &mut world));
+		}
+		assert_eq!(Some(bindings::Action::HardDrop), world.bindings.map(1).action(KeyCode::Space));
+		assert_eq!(None, world.bindings.map(0).action(KeyCode::Space));
+		assert!(world.bindings.map(1).hard_drop.len() == 2);
+
+		for key in [KeyCode::Escape, KeyCode::Escape, KeyCode::Down].iter() {
+			pause.key_down(*key, &mut world);
+		}
+		assert_eq!(Action::Quit, pause.key_down(KeyCode::Return, &mut world));
+		assert_eq!(Action::Resume, pause.key_down(KeyCode::P, &mut world));
+	}
+}
@@ -1,54 +1,78 @@
-
+use ggez::Context;
+use ggez::event::EventsLoop;
 use super::menu::Menu;
 use super::game::Game;
-use super::gameover::GameOver;
+use super::gameover::{GameOver, Choice};
 use super::window::Window;
+use super::super::model::world::World;
 
 // State Container
 
 pub trait Scene {
-	fn run(self) -> Window;
+	fn run(self, ctx: &mut Context, events_loop: &mut EventsLoop) -> Window;
 }
 
 // States
 
 impl Scene for Menu {
 
-	fn run(self) -> Window {
-		const VERSION: &str = env!("CARGO_PKG_VERSION");
-		leg::head("Tetris", Some("\u{1f579}\u{fe0f}"), Some(VERSION));
+	fn run(mut self, ctx: &mut Context, events_loop: &mut EventsLoop) -> Window {
 		leg::info("Menu", "Scene".into(), None);
-		Window::Game(self.into())
+		if self.start(ctx, events_loop) {
+			play(self.world)
+		}
+		else {
+			Window::Quit
+		}
 	}
 }
 
 impl Scene for Game {
 
-	fn run(mut self) -> Window {
+	fn run(mut self, ctx: &mut Context, events_loop: &mut EventsLoop) -> Window {
 		leg::info("Game", "Scene".into(), None);
-		self.start();
-		Window::GameOver(self.into())
+		self.start(ctx, events_loop);
+		if self.closed {
+			Window::Quit
+		}
+		else if self.restart {
+			play(self.world)
+		}
+		else {
+			Window::GameOver(self.into())
+		}
 	}
 }
 
 impl Scene for GameOver {
 
-	fn run(self) -> Window {
+	fn run(mut self, ctx: &mut Context, events_loop: &mut EventsLoop) -> Window {
 		leg::info("Game over", "Scene".into(), None);
-		Window::GameOver(self)
+		match self.start(ctx, events_loop) {
+			Choice::Restart => play(self.world),
+			Choice::NewSeed => {
+				self.world.seed = rand::random();
+				play(self.world)
+			}
+			Choice::Menu => Window::Menu(Menu::new(self.world)),
+			Choice::Quit => Window::Quit
+		}
 	}
 }
 
 // Transitions
 
-impl From<Menu> for Game {
-	fn from(value: Menu) -> Self {
-		Self::new(value.world)
+/// A new game of the world, unless one of its bots cannot be built
+fn play(world: World) -> Window {
+	match Game::new(world) {
+		Ok(game) => Window::Game(game),
+		Err(name) => Window::Failed(name)
 	}
 }
 
 impl From<Game> for GameOver {
 	fn from(value: Game) -> Self {
-		Self::new(value.world)
+		let results = value.results();
+		Self::new(value.world, results)
 	}
 }
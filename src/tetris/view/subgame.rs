@@ -8,75 +8,261 @@ use ggez::*;
 use ggez::event::*;
 use ggez::graphics::*;
 use na::*;
+use rand::{rngs, Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::iter;
+use super::super::model::bindings::{Action, Bindings, Handling};
 use super::super::model::board::Board;
-use super::super::ai::bot::Bot;
+use super::super::ai::coach::Coach;
+use super::super::ai::controller::{Controller, Player};
+use super::super::ai::finesse::Finesse;
+use super::super::ai::search;
 use super::super::model::shape::Shape;
+use super::theme::{Painter, Theme};
 use super::super::model::board::*;
+use super::super::model::fumen::{self, Page};
+use super::super::model::stats::{self, Stats};
+use super::super::model::world::{World, Mode, seed_from};
 
+/// Updates per second
+pub const FPS: u32 = 60;
 
+/// Frames between a piece locking and the next one falling on a human board (entry delay)
+pub const ARE: u32 = 6;
+
+/// Blocks across a board with its hold and next panels, and down
+const WIDTH: f32 = 23.0;
+const HEIGHT: f32 = 22.0;
+
+/// Where the board goes on the screen: the origin of its grid, and the size of the board with its panels
 struct TetrisDisplayConfig {
 	x: f32, y: f32,
 	w: f32, h: f32,
 	block_size: f32,
 }
 
+impl TetrisDisplayConfig {
+
+	/// The board in the middle of its panels, as large as the view allows without stretching
+	fn new(view: Rect) -> Self {
+		let block_size = (view.h / HEIGHT).min(view.w / WIDTH);
+		Self {
+			x: view.x + view.w / 2.0 - block_size * 6.0,
+			y: view.y + view.h / 2.0 - block_size * 11.0,
+			w: block_size * WIDTH,
+			h: block_size * HEIGHT,
+			block_size
+		}
+	}
+}
+
 pub struct SubGame {
+	/// Who plays the board
+	pub name: &'static str,
+	player: Player,
 	config: TetrisDisplayConfig,
 	board: Board,
-	bot: Option<Bot>,
-}
-
-#[derive(Clone, Copy)]
-pub enum Player {
-	Human,
-	Bot
+	bot: Option<Box<dyn Controller>>,
+	/// Whether the bot already chose where the current piece goes
+	asked: bool,
+	/// Where the bot wants the current piece, and the inputs left to get there
+	target: Option<Piece>,
+	inputs: VecDeque<Input>,
+	/// Score the bot gave its last target, if it scores them
+	evaluation: Option<f64>,
+	/// Key map of the human playing the board, and how its keys repeat
+	keys: usize,
+	handling: Handling,
+	/// Move keys held, the last one pressed last, and frames since it was pressed
+	held: Vec<Input>,
+	charge: u32,
+	/// Whether the soft drop key is held
+	soft_drop: bool,
+	/// Frames left before the next piece falls
+	entry: u32,
+	/// Pieces per second the bot plays at
+	pps: f32,
+	/// Frames between two inputs of the bot, and until the next one
+	interval: f32,
+	wait: f32,
+	/// Frames the piece takes to fall one row, and since it last fell
+	gravity: u32,
+	frame: u32,
+	mode: Mode,
+	pub stats: Stats,
+	/// Garbage lines received and not risen yet, and lines to send
+	garbage: usize,
+	sent: usize,
+	/// Chooses the column of the holes in the garbage
+	rng: rngs::SmallRng,
+	/// Whether garbage pushed blocks out of the playfield
+	crushed: bool,
+	history: Vec<Page>,
+	/// Reviews the placements of a human
+	coach: Option<Coach>,
+	/// Inputs of a human against the fewest needed
+	finesse: Option<Finesse>,
 }
 
 impl SubGame {
 
-	pub fn new(view: Rect, seed: [u8; 16], player: Player) -> Self {
+	/// Board of `player` in `view`, with the `keys`-th key map of the world if played by a human.
+	/// Fails with the name of the bot if it cannot be built (an engine that does not start).
+	pub fn new(view: Rect, world: &World, player: Player, keys: usize) -> Result<Self, String> {
+
+		let seed = seed_from(world.seed);
 
-		// Calculate values
-		let block_size = view.h / 22.0;
-		let x = view.x + view.w / 2.0 - block_size * 6.0;
-		let y = view.y + view.h / 2.0 - block_size * 11.0;
-		let w = view.w;
-		let h = view.h;
+		let name = player.name();
 
-		// Build bot
-		let bot = match player {
-			Player::Human=> None,
-			Player::Bot => Bot::new([0.0; 4]).into()
+		// Build bot (or coach of the human)
+		let (bot, mut coach, mut finesse) = match &player {
+			Player::Human => (None, world.coach.as_ref().map(Coach::new), Some(Finesse::new())),
+			Player::Bot(strategy) => (Some(strategy.build(seed).ok_or_else(|| name.to_string())?), None, None)
 		};
 
+		// Build board
+		let mut board = Board::new(seed);
+		if let Some(start) = &world.start {
+			board.grid = start.board.grid;
+			if start.piece {
+				board.set_current(start.board.current);
+			}
+		}
+		if let Some(coach) = &mut coach {
+			coach.observe(&board);
+		}
+		if let Some(finesse) = &mut finesse {
+			finesse.observe(&board);
+		}
+
 		// Build state
-		Self {
-			config: TetrisDisplayConfig { x, y, w, h, block_size },
+		Ok(Self {
+			name,
+			player,
+			config: TetrisDisplayConfig::new(view),
 			bot,
-			board: Board::new(seed),
-		}
+			asked: false,
+			target: None,
+			inputs: VecDeque::new(),
+			evaluation: None,
+			keys,
+			handling: world.bindings.map(keys).handling,
+			held: vec![],
+			charge: 0,
+			soft_drop: false,
+			entry: 0,
+			pps: world.pps,
+			interval: 0.0,
+			wait: 0.0,
+			gravity: world.gravity.max(1),
+			frame: 0,
+			mode: world.mode,
+			stats: Stats::default(),
+			garbage: 0,
+			sent: 0,
+			rng: rngs::SmallRng::from_seed(seed),
+			crushed: false,
+			board,
+			history: vec![],
+			coach,
+			finesse,
+		})
 	}
 
+	/// Advances one frame
 	pub fn update(&mut self) {
 
-		match &self.bot {
-			Some(bot) => {
-				let (x, rotation) = bot.ask(&self.board);
-				self.board.rotate_current(rotation);
-				self.board.move_current_to(x);
-			},
-			None => ()
+		if self.is_finished() {
+			return;
 		}
 
-		self.down();
+		self.stats.seconds += 1.0 / FPS as f32;
+
+		// Held keys keep charging while the next piece enters, to shift it as soon as it does
+		if self.entry > 0 {
+			self.entry -= 1;
+			self.charge = (self.charge + 1).min(self.handling.das.saturating_sub(1));
+			return;
+		}
+
+		self.play();
+		self.auto_shift();
+
+		// Soft dropping speeds gravity up, or drops the piece at once (without locking it)
+		let gravity = match (self.soft_drop, self.handling.soft_drop_factor) {
+			(true, 0) => {
+				while let Ok(piece) = down(&self.board, &self.board.current) {
+					self.board.current = piece;
+				}
+				self.gravity
+			}
+			(true, factor) => (self.gravity / factor).max(1),
+			(false, _) => self.gravity
+		};
+
+		self.frame += 1;
+		if self.frame >= gravity {
+			self.frame = 0;
+			self.input(Input::SoftDrop);
+
+			// The path of the bot started higher
+			if !self.inputs.is_empty() {
+				self.plan();
+			}
+		}
 	}
 
-	pub fn draw(&self, builder: &mut MeshBuilder) -> bool {
+	/// Lets the bot choose a target for a new piece and make the inputs due this frame
+	fn play(&mut self) {
+
+		if let Some(bot) = &mut self.bot {
 
-		// Check if you lost <3
-		if self.board.is_gameover() {
-			return true;
+			if !self.asked {
+				self.asked = true;
+				self.target = bot.ask(&self.board);
+				self.evaluation = bot.evaluation();
+				self.plan();
+				self.interval = FPS as f32 / (self.pps * self.inputs.len().max(1) as f32);
+				self.wait = self.interval;
+			}
+
+			self.wait -= 1.0;
+			while self.wait <= 0.0 {
+				match self.inputs.pop_front() {
+					Some(input) => self.input(input),
+					None => break
+				}
+				self.wait += self.interval;
+			}
 		}
+	}
+
+	/// Inputs taking the current piece to the target of the bot and locking it there
+	fn plan(&mut self) {
+		self.inputs = match self.target.and_then(|target| search::path(&self.board, &target)) {
+			Some(inputs) => inputs.into_iter().chain(iter::once(Input::SoftDrop)).collect(),
+			None => VecDeque::new()
+		};
+	}
+
+	/// Fits the board and its panels to a new view
+	pub fn resize(&mut self, view: Rect) {
+		self.config = TetrisDisplayConfig::new(view);
+	}
+
+	/// Draws the board and its panels in the theme of the painter, with the target of the bot
+	/// outlined when `focused`
+	pub fn draw(&self, painter: &mut Painter, focused: bool) {
+
+		let theme = painter.theme;
+
+		// Backdrop of the board and its panels
+		let pos = &self.pt_from_world_to_wnd([6.0 - WIDTH / 2.0, 0.0].into());
+		painter.builder.rectangle(
+			DrawMode::fill(),
+			Rect::new(pos.x, pos.y, self.config.w, self.config.h),
+			theme.panel.color()
+		);
 
 		// Draw board
 		for (index, cell) in self.board.grid.slice_range(1..21, 1..11).iter().enumerate() {
@@ -90,13 +276,13 @@ impl SubGame {
 			let sz = self.config.block_size;
 
 			// Get color
-			let color: Color;
-			let shape = Shape::from_index(*cell);
-			if shape.is_some() { color = shape.unwrap().color(); }
-			else if *cell == 8_u8 { color = Color::new(33.0 / 255.0, 33.0 / 255.0, 35.0 / 255.0, 1.0); }
-			else { color = Color::new(0.0, 0.0, 0.0, 0.0); }
+			let color = match Shape::from_index(*cell) {
+				Some(shape) => Some(theme.color(shape)),
+				None if *cell == 8_u8 => Some(theme.wall.color()),
+				None => None
+			};
 
-			builder.circle(
+			painter.builder.circle(
 				DrawMode::fill(),
 				Point2::new(pos.x + sz / 2.0, pos.y + sz / 2.0),
 				2.0,
@@ -104,83 +290,436 @@ impl SubGame {
 				Color::new(37.0 / 255.0, 37.0 / 255.0, 39.0 / 255.0, 0.6)
 			);
 
-			builder.rectangle(
-				DrawMode::fill(),
-				Rect::new(pos.x, pos.y, sz, sz),
-				color
-			);
+			if let Some(color) = color {
+				painter.block(Rect::new(pos.x, pos.y, sz, sz), color);
+			}
 		}
 
 		//panic!();
 
-		// Draw current piece
+		// Verdict of the coach on the last placement
+		if let Some(review) = self.coach.as_ref().and_then(|coach| coach.reviews.last()) {
+			let pos = &self.pt_from_world_to_wnd([-4.0, 6.0].into());
+			let color = if review.matched() { Color::new(0.3, 0.8, 0.4, 1.0) } else { Color::new(0.9, 0.3, 0.3, 1.0) };
+			painter.builder.circle(DrawMode::fill(), Point2::new(pos.x, pos.y), self.config.block_size / 4.0, 0.1, color);
+		}
+
+		// Finesse fault of the last placement
+		if let Some(placement) = self.finesse.as_ref().and_then(|finesse| finesse.last) {
+			if placement.is_fault() {
+				let pos = &self.pt_from_world_to_wnd([-3.0, 6.0].into());
+				let sz = self.config.block_size / 2.0;
+				painter.builder.rectangle(
+					DrawMode::fill(),
+					Rect::new(pos.x - sz / 2.0, pos.y - sz / 2.0, sz, sz),
+					Color::new(0.95, 0.65, 0.2, 1.0)
+				);
+			}
+		}
+
+		// Hold box (greyed out until the next piece), and next queue
+		if let Some(shape) = &self.board.hold {
+			let color = if self.board.can_hold() { theme.color(*shape) } else { Color::new(0.4, 0.4, 0.42, 1.0) };
+			self.draw_shape(painter, shape, [-2.25, 3.5].into(), 0.7, color);
+		}
+		for (index, shape) in self.board.collector.preview().iter().enumerate() {
+			let scale = if index == 0 { 0.8 } else { 0.6 };
+			self.draw_shape(painter, shape, [14.25, 3.5 + index as f32 * 3.0].into(), scale, theme.color(*shape));
+		}
+
+		// Where the bot is taking the piece
+		if let (true, Some(target)) = (focused, &self.target) {
+			let shape = &target.shape.value();
+			let sz = self.config.block_size;
+			for (index, _) in shape.iter().enumerate().filter(|(_, cell)| **cell != 0) {
+				let j = (index / shape.nrows()) + target.position.x;
+				let i = (index % shape.nrows()) + target.position.y;
+				let pos = &self.pt_from_world_to_wnd([j as f32, i as f32].into());
+				painter.builder.rectangle(DrawMode::stroke(2.0), Rect::new(pos.x + 1.0, pos.y + 1.0, sz - 2.0, sz - 2.0), theme.color(target.shape));
+			}
+		}
+
+		// Draw current piece (once it entered)
 		let position = &self.board.current.position;
 		let sz = self.config.block_size;
 		let shape = &self.board.current.shape.value();
-		let color = self.board.current.shape.color();
+		let color = theme.color(self.board.current.shape);
 
-		for (index, cell) in shape.iter().enumerate() {
+		for (index, _) in shape.iter().enumerate().filter(|(_, cell)| **cell != 0 && self.entry == 0) {
 			let j = (index / shape.nrows()) + position.x;
 			let i = (index % shape.nrows()) + position.y;
 			let pos = &self.pt_from_world_to_wnd([j as f32, i as f32].into());
 
 			// Draw cell
+			painter.block(Rect::new(pos.x, pos.y, sz, sz), color);
+		}
+	}
+
+	/// Drawn over the blocks (and the tiles): frozen boards are dimmed
+	pub fn draw_overlay(&self, builder: &mut MeshBuilder) {
+		if self.is_finished() {
+			let sz = self.config.block_size;
+			let pos = &self.pt_from_world_to_wnd([1.0, 1.0].into());
 			builder.rectangle(
 				DrawMode::fill(),
-				Rect::new(pos.x, pos.y, sz, sz),
-				if *cell == 0_u8 { Color::new(0.0, 0.0, 0.0, 0.0) } else { color }
+				Rect::new(pos.x, pos.y, sz * 10.0, sz * 20.0),
+				Color::new(0.0, 0.0, 0.0, 0.55)
 			);
 		}
+	}
+
+	/// A shape in its spawn orientation, centered on a point of the world
+	fn draw_shape(&self, painter: &mut Painter, shape: &Shape, center: Vec2, scale: f32, color: Color) {
+
+		let value = shape.value();
+		let cells: Vec<(usize, usize)> = value.iter().enumerate()
+			.filter(|(_, cell)| **cell != 0)
+			.map(|(index, _)| (index / value.nrows(), index % value.nrows()))
+			.collect();
 
-		false
+		let (min_j, max_j) = (cells.iter().map(|c| c.0).min().unwrap_or(0), cells.iter().map(|c| c.0).max().unwrap_or(0));
+		let (min_i, max_i) = (cells.iter().map(|c| c.1).min().unwrap_or(0), cells.iter().map(|c| c.1).max().unwrap_or(0));
+		let sz = self.config.block_size * scale;
+		let middle = self.pt_from_world_to_wnd(center);
+		let x = middle.x - (max_j - min_j + 1) as f32 * sz / 2.0;
+		let y = middle.y - (max_i - min_i + 1) as f32 * sz / 2.0;
+
+		for (j, i) in cells {
+			painter.block(Rect::new(x + (j - min_j) as f32 * sz, y + (i - min_i) as f32 * sz, sz - 1.0, sz - 1.0), color);
+		}
 	}
 
-	pub fn key_down_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+	/// Queues the text of the hold and next panels, and the stats under the hold box
+	/// (with the evaluation of the bot when `focused`)
+	pub fn draw_hud(&self, ctx: &mut Context, theme: &Theme, focused: bool) {
 
-		// TODO: Change order of the match keycode and put first the self.bot.is_none()
+		// Not smaller than a readable size on crowded grids
+		let size = self.config.block_size.max(8.0);
+		let white = theme.text.color();
+		let faded = theme.faded();
 
-		match keycode {
-			KeyCode::Down => {
-				match down(&self.board, &self.board.current) {
-					Ok(piece) => self.board.current = piece,
-					Err(BoardError::TouchingGround) => self.down(),
-					_ => unreachable!()
+		let mut queue = |content: String, scale: f32, color: Color, point: Vec2| {
+			let text = Text::new(TextFragment::new(content).scale(Scale::uniform(scale)).color(color));
+			let pos = self.pt_from_world_to_wnd(point);
+			graphics::queue_text(ctx, &text, [pos.x, pos.y], None);
+		};
+
+		queue("HOLD".into(), size * 0.8, faded, [-4.5, 1.0].into());
+		queue("NEXT".into(), size * 0.8, faded, [12.5, 1.0].into());
+
+		let seconds = self.stats.seconds;
+		let mut rows = vec![
+			("Score", self.stats.score.to_string()),
+			("Level", self.stats.level().to_string()),
+			("Lines", self.stats.lines.to_string()),
+			("Time", format!("{}:{:04.1}", (seconds / 60.0) as u32, seconds % 60.0)),
+			("PPS", format!("{:.2}", self.stats.pps()))
+		];
+		if let (true, Some(evaluation)) = (focused, self.evaluation) {
+			rows.push(("Eval", format!("{:.2}", evaluation)));
+		}
+		for (index, (label, value)) in rows.iter().enumerate() {
+			let y = 7.5 + index as f32 * 2.4;
+			queue(label.to_string(), size * 0.7, faded, [-4.5, y].into());
+			queue(value.clone(), size, white, [-4.5, y + 0.8].into());
+		}
+	}
+
+	/// Why the board stopped, over its center
+	pub fn draw_label(&self, ctx: &mut Context, theme: &Theme) -> GameResult<()> {
+
+		let label = if self.stats.winner {
+			"WINNER".to_string()
+		}
+		else if let Some(rank) = self.stats.topped_out {
+			format!("TOPPED OUT #{}", rank)
+		}
+		else if self.is_finished() {
+			"FINISHED".to_string()
+		}
+		else {
+			return Ok(());
+		};
+
+		let text = Text::new(TextFragment::new(label).scale(Scale::uniform(self.config.block_size * 1.2)).color(theme.text.color()));
+		let (w, h) = text.dimensions(ctx);
+		let center = &self.pt_from_world_to_wnd([6.0, 11.0].into());
+		graphics::draw(ctx, &text, DrawParam::default().dest([center.x - w as f32 / 2.0, center.y - h as f32 / 2.0]))
+	}
+
+	/// Moves the piece again while a move key is held: after `das` frames, every `arr` frames
+	/// (to the wall at once with an `arr` of 0)
+	fn auto_shift(&mut self) {
+
+		let direction = match self.held.last() {
+			Some(direction) => *direction,
+			None => return
+		};
+
+		self.charge += 1;
+		if self.charge < self.handling.das {
+			return;
+		}
+
+		if self.handling.arr == 0 {
+			while let Ok(piece) = apply(&self.board, &self.board.current, direction) {
+				self.board.current = piece;
+			}
+		}
+		else if (self.charge - self.handling.das).is_multiple_of(self.handling.arr) {
+			self.input(direction);
+		}
+	}
+
+	/// Plays the action the key is bound to in the key map of the board (if played by a human).
+	/// Repeats of the system are ignored, held keys repeat with the handling of the player.
+	pub fn key_down_event(&mut self, keycode: KeyCode, bindings: &Bindings, repeat: bool) {
+
+		if repeat {
+			return;
+		}
+
+		// Nothing to move while the next piece enters, but the move keys start charging
+		let playing = self.is_human() && !self.is_finished();
+		let active = playing && self.entry == 0;
+		let action = if self.is_human() { bindings.map(self.keys).action(keycode) } else { None };
+
+		match action {
+			Some(Action::Left) | Some(Action::Right) if playing => {
+				let direction = if action == Some(Action::Left) { Input::Left } else { Input::Right };
+				self.held.retain(|held| *held != direction);
+				self.held.push(direction);
+				self.charge = 0;
+				if active {
+					self.press(direction);
 				}
 			}
-			KeyCode::Left => {
-				if self.bot.is_none() {
-					if let Ok(piece) = left(&self.board, &self.board.current) { self.board.current = piece }
+			Some(Action::SoftDrop) if playing => {
+				self.soft_drop = true;
+				if active {
+					self.press(Input::SoftDrop);
 				}
 			}
-			KeyCode::Right => {
-				if self.bot.is_none() {
-					if let Ok(piece) = right(&self.board, &self.board.current) { self.board.current = piece }
+			Some(_) if !active => (),
+			Some(Action::HardDrop) if playing => self.hard_drop(),
+			Some(Action::RotateCw) if playing => self.press(Input::Rotate),
+			Some(Action::RotateCcw) if playing => self.press(Input::RotateCcw),
+			Some(Action::Rotate180) if playing => self.press(Input::Rotate180),
+			Some(Action::Hold) if playing => self.hold(),
+			Some(_) => (),
+			None => self.command(keycode)
+		}
+	}
+
+	/// Stops the repeat of a move key (the other one takes over if held) or the soft drop
+	pub fn key_up_event(&mut self, keycode: KeyCode, bindings: &Bindings) {
+
+		if !self.is_human() {
+			return;
+		}
+
+		match bindings.map(self.keys).action(keycode) {
+			Some(Action::Left) => self.release(Input::Left),
+			Some(Action::Right) => self.release(Input::Right),
+			Some(Action::SoftDrop) => self.soft_drop = false,
+			_ => ()
+		}
+	}
+
+	fn release(&mut self, direction: Input) {
+		if self.held.last() == Some(&direction) {
+			self.charge = 0;
+		}
+		self.held.retain(|held| *held != direction);
+	}
+
+	/// Repeat of the held keys, from the key map of the board
+	pub fn set_handling(&mut self, bindings: &Bindings) {
+		self.handling = bindings.map(self.keys).handling;
+	}
+
+	/// Keys of every board, whoever plays it
+	fn command(&mut self, keycode: KeyCode) {
+		match keycode {
+			KeyCode::F => {
+				leg::info(&fumen::encode(&self.history), "Fumen".into(), None);
+			}
+			KeyCode::C => self.report(),
+			_ => ()
+		}
+	}
+
+	/// Drops the piece as far as it goes and locks it there
+	fn hard_drop(&mut self) {
+		let pieces = self.stats.pieces;
+		while self.stats.pieces == pieces {
+			self.input(Input::SoftDrop);
+		}
+	}
+
+	/// Puts the current piece aside, once per piece
+	fn hold(&mut self) {
+
+		if !self.board.hold() {
+			return;
+		}
+
+		self.asked = false;
+		self.target = None;
+		self.inputs.clear();
+		self.frame = 0;
+
+		if let Some(coach) = &mut self.coach {
+			coach.observe(&self.board);
+		}
+		if let Some(finesse) = &mut self.finesse {
+			finesse.observe(&self.board);
+		}
+	}
+
+	/// Input of the human, counted for finesse
+	fn press(&mut self, input: Input) {
+		if let Some(finesse) = &mut self.finesse {
+			finesse.input(input);
+		}
+		self.input(input);
+	}
+
+	/// Moves the current piece, soft dropping it on the stack locks it
+	fn input(&mut self, input: Input) {
+		match apply(&self.board, &self.board.current, input) {
+			Ok(piece) => self.board.current = piece,
+			Err(BoardError::TouchingGround) => self.lock(),
+			Err(_) => ()
+		}
+	}
+
+	fn lock(&mut self) {
+
+		self.history.push(Page::new(self.board.clone()));
+		self.asked = false;
+		self.target = None;
+		self.inputs.clear();
+		self.frame = 0;
+		// Bots keep the pace of their pieces per second
+		self.entry = if self.is_human() { ARE } else { 0 };
+
+		let played = self.board.current;
+		let lines = self.board.lock_current();
+		if lines > 0 {
+			leg::success("Line completed", "\u{1f37b}".into(), None);
+		}
+		self.stats.lock(lines);
+
+		// Lines sent cancel the garbage received first, which rises if no line was cleared
+		if self.mode == Mode::Versus {
+			let sent = stats::garbage(lines);
+			let cancelled = sent.min(self.garbage);
+			self.garbage -= cancelled;
+			self.sent += sent - cancelled;
+			self.stats.sent += sent - cancelled;
+			if lines == 0 && self.garbage > 0 {
+				let hole = self.rng.gen_range(2, 10);
+				self.crushed = !self.board.add_garbage(self.garbage, hole);
+				self.garbage = 0;
+			}
+		}
+
+		if let Some(coach) = &mut self.coach {
+			if let Some(review) = coach.review(played) {
+				if review.matched() {
+					leg::success("Same placement as the coach", "Coach".into(), None);
+				}
+				else {
+					leg::warn(&format!(
+						"The coach prefers another placement ({:.2} against {:.2})",
+						review.played_score, review.best_score
+					), "Coach".into(), None);
 				}
 			}
-			KeyCode::Up => {
-				if self.bot.is_none() {
-					if let Ok(piece) = rotate(&self.board, &self.board.current) {
-						self.board.current = piece
-					}
+			coach.observe(&self.board);
+		}
+
+		if let Some(finesse) = &mut self.finesse {
+			if let Some(placement) = finesse.lock(&played) {
+				if placement.is_fault() {
+					leg::warn(&format!(
+						"Finesse fault: {} inputs where {} were enough",
+						placement.used, placement.minimal
+					), "Finesse".into(), None);
 				}
 			}
-			_ => ()
+			finesse.observe(&self.board);
+		}
+
+		if self.is_human() && self.is_topped_out() {
+			self.report();
 		}
 	}
 
-	fn down(&mut self) {
-		self.board.current = down(&self.board, &self.board.current)
-			.unwrap_or_else(|_| {
-				self.board.place_current_piece();
-				self.board.remove_full_lines();
-				self.board.collector.next();
-				let current = self.board.collector.get_current();
-				Piece::new(self.board.grid.ncols() / 2 - current.w() / 2, current)
-			});
+	/// Pieces per second of the bot, from its next piece on
+	pub fn set_pps(&mut self, pps: f32) {
+		self.pps = pps;
+	}
+
+	/// Garbage sent by another board, rising after the next piece that clears no line
+	pub fn receive(&mut self, lines: usize) {
+		self.garbage += lines;
+		self.stats.received += lines;
+	}
+
+	/// Garbage lines to send to the other boards
+	pub fn take_sent(&mut self) -> usize {
+		std::mem::replace(&mut self.sent, 0)
+	}
+
+	/// Finesse of the human, their accuracy against the coach and their worst placements (as fumen diagrams)
+	fn report(&self) {
+		if let Some(finesse) = &self.finesse {
+			leg::info(&format!(
+				"{} of {} placements with extra inputs ({:.0}% clean, {} extra inputs)",
+				finesse.faults, finesse.pieces, finesse.accuracy() * 100.0, finesse.extra
+			), "Finesse".into(), None);
+		}
+		if let Some(coach) = &self.coach {
+			leg::info(&format!(
+				"{:.0}% of {} placements matched",
+				coach.accuracy() * 100.0, coach.reviews.len()
+			), "Coach".into(), None);
+			for review in coach.worst(5) {
+				leg::info(&format!(
+					"Placement {}: lost {:.2}, {}",
+					review.index + 1, review.loss(), review.fumen()
+				), "Coach".into(), None);
+			}
+		}
+	}
+
+	/// The stack reached the top, or the new piece has no room
+	pub fn is_topped_out(&self) -> bool {
+		self.crushed || self.board.is_gameover() || collides(&self.board, &self.board.current)
+	}
+
+	/// Topped out, won, or reached the goal of the mode
+	pub fn is_finished(&self) -> bool {
+		self.is_topped_out()
+			|| self.stats.winner
+			|| self.mode.lines().is_some_and(|lines| self.stats.lines >= lines)
+			|| self.mode.seconds().is_some_and(|seconds| self.stats.seconds >= seconds)
 	}
 
 	// Helpers
 
+	pub fn is_human(&self) -> bool {
+		matches!(self.player, Player::Human)
+	}
+
+	/// Whether a point of the window falls on the board or its panels
+	pub fn contains(&self, x: f32, y: f32) -> bool {
+		let point = self.pt_from_wnd_to_world(Vec2::new(x, y));
+		(point.x - 6.0).abs() <= WIDTH / 2.0 && point.y >= 0.0 && point.y <= HEIGHT
+	}
+
 	fn pt_from_wnd_to_world(&self, point: Vec2) -> Vec2 {
 		let x = (point.x - self.config.x) / self.config.block_size;
 		let y = (point.y - self.config.y) / self.config.block_size;
@@ -194,3 +733,81 @@ impl SubGame {
 	}
 }
 
+#[cfg(test)]
+mod test {
+
+	use super::*;
+	use super::super::super::model::bindings::Bindings;
+	use super::super::super::ai::controller::Strategy;
+
+	#[test]
+	fn subgame_resize_test() {
+		let world = World::for_test();
+		let mut game = SubGame::new(Rect::new(0.0, 0.0, 460.0, 440.0), &world, Player::Bot(Strategy::Greedy), 0).unwrap();
+		assert_eq!(20.0, game.config.block_size);
+
+		// A narrow view: blocks as wide as it allows, the board centered in both directions
+		game.resize(Rect::new(100.0, 50.0, 230.0, 440.0));
+		let config = &game.config;
+		assert_eq!((10.0, 230.0, 220.0), (config.block_size, config.w, config.h));
+		assert_eq!((215.0, 270.0), (config.x + 6.0 * 10.0, config.y + 11.0 * 10.0));
+		// No board for an engine that does not start
+		let engine = Player::Bot(Strategy::External("./no-such-engine".into()));
+		assert_eq!(Some("tbp".to_string()), SubGame::new(Rect::new(0.0, 0.0, 460.0, 440.0), &world, engine, 0).err());
+	}
+
+	#[test]
+	fn subgame_pace_test() {
+		let world = World {
+			pps: 4.0,
+			..World::for_test()
+		};
+		let mut game = SubGame::new(Rect::new(0.0, 0.0, 460.0, 440.0), &world, Player::Bot(Strategy::Greedy), 0).unwrap();
+
+		// Five seconds at 4 pieces per second, with no entry delay for the bot
+		for _ in 0..5 * FPS {
+			game.update();
+		}
+		assert_eq!(0, game.entry);
+		assert!(game.stats.pieces >= 19);
+	}
+
+	#[test]
+	fn subgame_handling_test() {
+		let mut bindings = Bindings::default();
+		bindings.players[0].handling = Handling { das: 3, arr: 0, soft_drop_factor: 20 };
+		let world = World {
+			bindings,
+			..World::for_test()
+		};
+		let mut game = SubGame::new(Rect::new(0.0, 0.0, 460.0, 440.0), &world, Player::Human, 0).unwrap();
+		let x = game.board.current.position.x;
+		let wall = |game: &SubGame| apply(&game.board, &game.board.current, Input::Left).is_err();
+
+		// One move on the press, repeats of the system ignored, then to the wall once charged
+		game.key_down_event(KeyCode::Left, &world.bindings, false);
+		game.key_down_event(KeyCode::Left, &world.bindings, true);
+		assert_eq!(x - 1, game.board.current.position.x);
+		game.update();
+		game.update();
+		assert!(!wall(&game));
+		game.update();
+		assert!(wall(&game));
+
+		// Right takes over while Left is held, and gives it back when released
+		game.key_down_event(KeyCode::Right, &world.bindings, false);
+		assert_eq!(Some(&Input::Right), game.held.last());
+		game.key_up_event(KeyCode::Right, &world.bindings);
+		assert_eq!((vec![Input::Left], 0), (game.held.clone(), game.charge));
+
+		// Charged during the entry of the next piece, which shifts as soon as it falls
+		game.key_down_event(KeyCode::Space, &world.bindings, false);
+		assert_eq!((1, ARE), (game.stats.pieces, game.entry));
+		for _ in 0..ARE {
+			game.update();
+		}
+		assert!(!wall(&game));
+		game.update();
+		assert!(wall(&game));
+	}
+}
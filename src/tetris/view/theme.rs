@@ -0,0 +1,255 @@
+extern crate serde;
+extern crate toml;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use ggez::graphics::{Color, DrawMode, DrawParam, Image, MeshBuilder, Rect};
+use ggez::graphics::spritebatch::SpriteBatch;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error;
+use super::super::model::shape::Shape;
+
+/// A colour, saved as `#rrggbb`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+
+	pub fn from_hex(hex: &str) -> Option<Self> {
+		let digits = hex.strip_prefix('#')?;
+		if digits.len() != 6 {
+			return None;
+		}
+		let channel = |index: usize| u8::from_str_radix(digits.get(index..index + 2)?, 16).ok();
+		Some(Rgb(channel(0)?, channel(2)?, channel(4)?))
+	}
+
+	pub fn color(self) -> Color {
+		Color::from_rgb(self.0, self.1, self.2)
+	}
+}
+
+impl fmt::Display for Rgb {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+	}
+}
+
+impl Serialize for Rgb {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&self.to_string())
+	}
+}
+
+impl<'de> Deserialize<'de> for Rgb {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let hex = String::deserialize(deserializer)?;
+		Rgb::from_hex(&hex).ok_or_else(|| D::Error::custom(format!("`{}` is not a #rrggbb colour", hex)))
+	}
+}
+
+/// How a block is drawn
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+	Flat,
+	/// Lit from the top left
+	Bevel,
+	/// Hollow, barely filled
+	Outline,
+	/// The tile of the theme tinted with the colour of the block
+	Image
+}
+
+/// Colours and block style of the game, saved as TOML
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Theme {
+	pub name: String,
+	pub style: Style,
+	pub background: Rgb,
+	/// Behind each board and its panels
+	pub panel: Rgb,
+	/// Walls and garbage
+	pub wall: Rgb,
+	pub text: Rgb,
+	/// Highlighted items of the menus
+	pub highlight: Rgb,
+	/// Colours of I, J, L, O, S, T and Z
+	pub pieces: [Rgb; 7],
+	/// Image of the tiles (a file of `.assets`), for the image style
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tile: Option<String>
+}
+
+const CLASSIC: [Rgb; 7] = [
+	Rgb(249, 35, 56), Rgb(201, 115, 255), Rgb(28, 118, 188), Rgb(254, 227, 86),
+	Rgb(83, 213, 4), Rgb(54, 224, 255), Rgb(248, 147, 29)
+];
+
+const GUIDELINE: [Rgb; 7] = [
+	Rgb(0, 220, 230), Rgb(30, 80, 230), Rgb(245, 145, 20), Rgb(245, 220, 30),
+	Rgb(60, 210, 60), Rgb(170, 60, 220), Rgb(235, 40, 50)
+];
+
+/// Okabe and Ito's palette, told apart with any colour vision
+const OKABE_ITO: [Rgb; 7] = [
+	Rgb(86, 180, 233), Rgb(0, 114, 178), Rgb(230, 159, 0), Rgb(240, 228, 66),
+	Rgb(0, 158, 115), Rgb(204, 121, 167), Rgb(213, 94, 0)
+];
+
+impl Default for Theme {
+	fn default() -> Self {
+		Self {
+			name: "Classic".into(),
+			style: Style::Flat,
+			background: Rgb(28, 28, 30),
+			panel: Rgb(22, 22, 24),
+			wall: Rgb(33, 33, 35),
+			text: Rgb(230, 230, 235),
+			highlight: Rgb(54, 224, 255),
+			pieces: CLASSIC,
+			tile: None
+		}
+	}
+}
+
+impl Theme {
+
+	/// Themes shipped with the game, the default one first
+	pub fn builtin() -> Vec<Self> {
+		vec![
+			Self::default(),
+			Self {
+				name: "Guideline".into(),
+				style: Style::Bevel,
+				pieces: GUIDELINE,
+				..Self::default()
+			},
+			Self {
+				name: "High contrast".into(),
+				style: Style::Bevel,
+				background: Rgb(0, 0, 0),
+				panel: Rgb(0, 0, 0),
+				wall: Rgb(128, 128, 128),
+				text: Rgb(255, 255, 255),
+				highlight: Rgb(240, 228, 66),
+				pieces: OKABE_ITO,
+				tile: None
+			},
+			Self {
+				name: "Neon".into(),
+				style: Style::Outline,
+				background: Rgb(8, 8, 16),
+				panel: Rgb(14, 14, 26),
+				wall: Rgb(40, 40, 70),
+				pieces: GUIDELINE,
+				..Self::default()
+			},
+			Self {
+				name: "Tiles".into(),
+				style: Style::Image,
+				pieces: GUIDELINE,
+				tile: Some("tile.png".into()),
+				..Self::default()
+			}
+		]
+	}
+
+	/// Built-in theme by name (any case)
+	pub fn find(name: &str) -> Option<Self> {
+		Self::builtin().into_iter().find(|theme| theme.name.eq_ignore_ascii_case(name))
+	}
+
+	pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		toml::from_str(&fs::read_to_string(path)?)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+	}
+
+	pub fn color(&self, shape: Shape) -> Color {
+		self.pieces[shape.index() as usize - 1].color()
+	}
+
+	/// Text a bit dimmer than the rest
+	pub fn faded(&self) -> Color {
+		let text = self.text.color();
+		Color::new(text.r, text.g, text.b, 0.6)
+	}
+}
+
+/// Lighter (`amount` > 0) or darker (`amount` < 0) colour
+fn shade(color: Color, amount: f32) -> Color {
+	let mix = |channel: f32| if amount > 0.0 { channel + (1.0 - channel) * amount } else { channel * (1.0 + amount) };
+	Color::new(mix(color.r), mix(color.g), mix(color.b), color.a)
+}
+
+/// Draws the blocks in the style of the theme: shapes go to the mesh, tiles to the batch
+pub struct Painter<'a> {
+	pub builder: &'a mut MeshBuilder,
+	pub theme: &'a Theme,
+	/// Tiles of the image style, with the size of the image (bevelled blocks without it)
+	pub batch: Option<(&'a mut SpriteBatch, f32, f32)>
+}
+
+impl<'a> Painter<'a> {
+
+	pub fn new(builder: &'a mut MeshBuilder, theme: &'a Theme, batch: Option<&'a mut SpriteBatch>, tile: Option<&Image>) -> Self {
+		let batch = batch.and_then(|batch| tile.map(|tile| (batch, tile.width() as f32, tile.height() as f32)));
+		Self { builder, theme, batch }
+	}
+
+	pub fn block(&mut self, rect: Rect, color: Color) {
+
+		let border = (rect.w / 6.0).max(1.0);
+
+		match (self.theme.style, &mut self.batch) {
+			(Style::Flat, _) => {
+				self.builder.rectangle(DrawMode::fill(), rect, color);
+			}
+			(Style::Image, Some((batch, w, h))) => {
+				batch.add(DrawParam::default().dest([rect.x, rect.y]).scale([rect.w / *w, rect.h / *h]).color(color));
+			}
+			(Style::Bevel, _) | (Style::Image, None) => {
+				self.builder.rectangle(DrawMode::fill(), rect, color);
+				self.builder.rectangle(DrawMode::fill(), Rect::new(rect.x, rect.y + rect.h - border, rect.w, border), shade(color, -0.35));
+				self.builder.rectangle(DrawMode::fill(), Rect::new(rect.x + rect.w - border, rect.y, border, rect.h), shade(color, -0.35));
+				self.builder.rectangle(DrawMode::fill(), Rect::new(rect.x, rect.y, rect.w, border), shade(color, 0.4));
+				self.builder.rectangle(DrawMode::fill(), Rect::new(rect.x, rect.y, border, rect.h), shade(color, 0.4));
+			}
+			(Style::Outline, _) => {
+				self.builder.rectangle(DrawMode::fill(), rect, Color::new(color.r, color.g, color.b, 0.2));
+				let inset = border / 2.0;
+				self.builder.rectangle(
+					DrawMode::stroke(border),
+					Rect::new(rect.x + inset, rect.y + inset, rect.w - border, rect.h - border),
+					color
+				);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+
+	use super::*;
+
+	#[test]
+	fn theme_toml_test() {
+		assert_eq!(Some(Rgb(0, 114, 178)), Rgb::from_hex("#0072b2"));
+		assert_eq!(None, Rgb::from_hex("0072b2"));
+		assert_eq!(None, Rgb::from_hex("#0072g2"));
+
+		// Every built-in theme goes through the file format, with its own colour for each shape
+		for theme in Theme::builtin() {
+			let content = toml::to_string(&theme).unwrap();
+			assert_eq!(theme, toml::from_str(&content).unwrap());
+			for (index, colour) in theme.pieces.iter().enumerate() {
+				assert!(!theme.pieces[index + 1..].contains(colour));
+			}
+		}
+		assert_eq!(Some(Style::Image), Theme::find("tiles").map(|theme| theme.style));
+		assert!(toml::from_str::<Theme>("name = \"Bad\"\nstyle = \"round\"").is_err());
+	}
+}
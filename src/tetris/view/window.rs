@@ -1,4 +1,7 @@
-
+use ggez::{Context, ContextBuilder, graphics};
+use std::env;
+use std::path::PathBuf;
+use ggez::graphics::Rect;
 use super::super::model::world::World;
 use super::menu::Menu;
 use super::game::Game;
@@ -8,7 +11,11 @@ use super::scene::Scene;
 pub enum Window {
 	Menu(Menu),
 	Game(Game),
-	GameOver(GameOver)
+	GameOver(GameOver),
+	/// The window was closed
+	Quit,
+	/// A bot of the game could not be built
+	Failed(String)
 }
 
 impl Window {
@@ -17,13 +24,56 @@ impl Window {
 		Window::Menu(Menu::new(world))
 	}
 
-	pub fn run(mut self) {
+	/// Runs the scenes one after another in the same window.
+	/// Fails with the name of a bot that could not be built.
+	pub fn run(mut self) -> Result<(), String> {
+
+		let config = match &self {
+			Window::Menu(m) => m.world.config.clone(),
+			Window::Game(g) => g.world.config.clone(),
+			Window::GameOver(o) => o.world.config.clone(),
+			Window::Quit => return Ok(()),
+			Window::Failed(name) => return Err(name.clone())
+		};
+
+		let (ctx, events_loop) = &mut assets()
+			.into_iter()
+			.fold(ContextBuilder::new("Tetris", "Mr.Robb"), |builder, path| builder.add_resource_path(path))
+			.conf(config)
+			.with_conf_file(true)
+			.build()
+			.expect(" ._. Could not create ggez context");
+
+		const VERSION: &str = env!("CARGO_PKG_VERSION");
+		leg::head("Tetris", Some("\u{1f579}\u{fe0f}"), Some(VERSION));
+
 		loop {
+			// Every scene runs its own loop until it quits
+			ctx.continuing = true;
+			let (width, height) = graphics::drawable_size(ctx);
+			fit(ctx, width, height);
 			self = match self {
-				Window::Menu(m) => m.run(),
-				Window::Game(g) => g.run(),
-				Window::GameOver(o) => { o.run(); break; },
+				Window::Menu(m) => m.run(ctx, events_loop),
+				Window::Game(g) => g.run(ctx, events_loop),
+				Window::GameOver(o) => o.run(ctx, events_loop),
+				Window::Quit => return Ok(()),
+				Window::Failed(name) => return Err(name)
 			}
 		}
 	}
 }
+
+/// Where the tiles of the themes are looked up, besides the `resources` of ggez:
+/// the `.assets` next to the executable, and the ones of the crate under `cargo run`
+fn assets() -> Vec<PathBuf> {
+	let exe = env::current_exe().ok().and_then(|exe| exe.parent().map(|dir| dir.join(".assets")));
+	let crate_dir = env::var_os("CARGO_MANIFEST_DIR").map(|dir| PathBuf::from(dir).join(".assets"));
+	exe.into_iter().chain(crate_dir).collect()
+}
+
+/// Maps the screen coordinates to the pixels of the window, so the scenes
+/// lay out in pixels whatever the size and the HiDPI factor of the window
+pub fn fit(ctx: &mut Context, width: f32, height: f32) {
+	graphics::set_screen_coordinates(ctx, Rect::new(0.0, 0.0, width, height))
+		.expect("Could not set the screen coordinates");
+}